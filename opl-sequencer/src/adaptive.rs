@@ -0,0 +1,390 @@
+//! Music that follows what's going on, by switching between sections.
+//!
+//! Every section is its own [Sequence], like "calm" and "tense".
+//! A section loops or goes on to another section when it's done,
+//! and the application can ask to switch to another section at any time.
+//! The switch happens at the next switch point, so the music doesn't get cut off in the middle of a bar.
+
+use crate::backend::SynthBackend;
+use crate::dynamics::MAX_VOLUME;
+use crate::observer::Observer;
+use crate::sequencer::{Sequence, SequenceError};
+use alloc::vec::Vec;
+use opl_driver::hl::Note;
+
+/// When a requested section switch may happen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwitchPoint {
+    /// On the next advance
+    Immediately,
+    /// At the start of the next bar, counted from the start of the current section.
+    /// The length of the bar is in ticks and can't be 0.
+    Bar { length: u32 },
+    /// Right after the next [Action::Marker](crate::sequencer::Action::Marker) of the current section
+    Marker,
+}
+
+/// A node of the section graph.
+///
+/// A section is done after its last event, so end it with a [Marker](crate::sequencer::Action::Marker)
+/// if it should be longer than its last note off.
+pub struct Section<B: SynthBackend, const N: usize, U = ()> {
+    sequence: Sequence<B, N, U>,
+    next: Option<usize>,
+}
+
+impl<B: SynthBackend, const N: usize, U> Section<B, N, U> {
+    /// A section that loops until another section is requested
+    pub fn new(sequence: Sequence<B, N, U>) -> Self {
+        Self {
+            sequence,
+            next: None,
+        }
+    }
+
+    /// Makes the section go on to the section with the index when it's done, instead of looping.
+    /// The index is checked when the sections are given to the [SectionPlayer].
+    pub fn then(mut self, next: usize) -> Self {
+        self.next = Some(next);
+        self
+    }
+}
+
+/// Plays a graph of sections and switches between them on request
+pub struct SectionPlayer<B: SynthBackend, const N: usize, U = ()> {
+    sections: Vec<Section<B, N, U>>,
+    switch_point: SwitchPoint,
+    current: usize,
+    sequence: Sequence<B, N, U>,
+    requested: Option<usize>,
+    position: u32,
+    section_start: u32,
+    volume: u8,
+}
+
+impl<B: SynthBackend, const N: usize, U: Clone> SectionPlayer<B, N, U> {
+    /// Creates a player that starts with the first section
+    pub fn new(sections: Vec<Section<B, N, U>>, switch_point: SwitchPoint) -> Self {
+        assert!(!sections.is_empty(), "There must be at least one section");
+        if let SwitchPoint::Bar { length } = switch_point {
+            assert!(length > 0, "A bar must be at least one tick long");
+        }
+        for section in sections.iter() {
+            if let Some(next) = section.next {
+                assert!(next < sections.len(), "There is no section {}", next);
+            }
+        }
+
+        Self {
+            sequence: sections[0].sequence.clone(),
+            sections,
+            switch_point,
+            current: 0,
+            requested: None,
+            position: 0,
+            section_start: 0,
+            volume: MAX_VOLUME,
+        }
+    }
+
+    /// The index of the section that is playing
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// The section that will be switched to at the next switch point
+    pub fn requested(&self) -> Option<usize> {
+        self.requested
+    }
+
+    /// Asks to switch to the section at the next switch point.
+    /// A later request replaces an earlier one that hasn't happened yet.
+    pub fn request(&mut self, section: usize) {
+        assert!(
+            section < self.sections.len(),
+            "There is no section {}",
+            section
+        );
+        self.requested = Some(section);
+    }
+
+    /// The timestamp that was run last
+    pub fn position(&self) -> u32 {
+        self.position
+    }
+
+    /// The master volume, from 0 to [MAX_VOLUME]
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    /// Sets the master volume of the player. It's kept when the player switches to another section.
+    /// See [Sequence::set_volume].
+    pub fn set_volume(
+        &mut self,
+        backend: &mut B,
+        volume: u8,
+    ) -> Result<(), SequenceError<B::Error>> {
+        self.volume = volume.min(MAX_VOLUME);
+        self.sequence.set_volume(backend, volume)
+    }
+
+    /// See [Sequence::take_tempo_change]
+    pub fn take_tempo_change(&mut self) -> Option<u32> {
+        self.sequence.take_tempo_change()
+    }
+
+    /// Moves the position forward by the amount of ticks and runs the music there.
+    /// Returns false when a section ends and there is nothing to go on with, which can only happen with empty sections.
+    pub fn advance(
+        &mut self,
+        backend: &mut B,
+        ticks: u32,
+    ) -> Result<bool, SequenceError<B::Error>> {
+        self.advance_observed(backend, ticks, &mut ())
+    }
+
+    /// Like [Self::advance], but tells the observer what the sequence is doing.
+    /// See [Sequence::run_observed].
+    pub fn advance_observed<O: Observer<U> + ?Sized>(
+        &mut self,
+        backend: &mut B,
+        ticks: u32,
+        observer: &mut O,
+    ) -> Result<bool, SequenceError<B::Error>> {
+        self.position = self.position.wrapping_add(ticks);
+
+        if let (Some(section), Some(0)) = (self.requested, self.ticks_to_bar()) {
+            self.switch(backend, section)?;
+        }
+
+        let mut watch = MarkerWatch {
+            observer,
+            passed: false,
+        };
+        let mut running = self
+            .sequence
+            .run_observed(backend, self.position, &mut watch)?;
+
+        if let (Some(section), SwitchPoint::Marker, true) =
+            (self.requested, self.switch_point, watch.passed)
+        {
+            self.switch(backend, section)?;
+            running = self
+                .sequence
+                .run_observed(backend, self.position, &mut *watch.observer)?;
+        }
+
+        if !running {
+            let section = self
+                .requested
+                .or(self.sections[self.current].next)
+                .unwrap_or(self.current);
+            self.switch(backend, section)?;
+            running = self
+                .sequence
+                .run_observed(backend, self.position, watch.observer)?;
+        }
+
+        Ok(running)
+    }
+
+    /// The amount of ticks from the position to the next moment the player has to be advanced to
+    pub fn ticks_to_next_event(&self) -> Option<u32> {
+        let next_event = self
+            .sequence
+            .next_timestamp()
+            .map(|next| next.saturating_sub(self.position));
+
+        match (next_event, self.requested.and(self.ticks_to_bar())) {
+            (Some(event), Some(bar)) => Some(event.min(bar)),
+            (event, bar) => event.or(bar),
+        }
+    }
+
+    /// The amount of ticks to the next switch point if that's a bar line or the next tick.
+    /// Is 0 when the position is on the switch point.
+    fn ticks_to_bar(&self) -> Option<u32> {
+        match self.switch_point {
+            SwitchPoint::Immediately => Some(0),
+            SwitchPoint::Bar { length } => {
+                let into_bar = self.position.wrapping_sub(self.section_start) % length;
+                Some(if into_bar == 0 { 0 } else { length - into_bar })
+            }
+            SwitchPoint::Marker => None,
+        }
+    }
+
+    /// Releases all notes of the current section and starts the section at the position
+    fn switch(&mut self, backend: &mut B, section: usize) -> Result<(), SequenceError<B::Error>> {
+        for (channel, _) in self.sequence.sounding_notes() {
+            backend.stop_note(channel).map_err(SequenceError::Backend)?;
+        }
+
+        self.sequence = self.sections[section].sequence.clone();
+        self.sequence.start_at(self.position);
+        self.sequence.init_volume(self.volume);
+        self.current = section;
+        self.section_start = self.position;
+        self.requested = None;
+
+        Ok(())
+    }
+}
+
+/// Passes everything on to the observer and remembers if a marker went by
+struct MarkerWatch<'a, O: ?Sized> {
+    observer: &'a mut O,
+    passed: bool,
+}
+
+impl<U, O: Observer<U> + ?Sized> Observer<U> for MarkerWatch<'_, O> {
+    fn on_marker(&mut self, id: u32, timestamp: u32) {
+        self.passed = true;
+        self.observer.on_marker(id, timestamp);
+    }
+
+    fn on_note_on(&mut self, channel: usize, note: Note, timestamp: u32) {
+        self.observer.on_note_on(channel, note, timestamp);
+    }
+
+    fn on_note_off(&mut self, channel: usize, timestamp: u32) {
+        self.observer.on_note_off(channel, timestamp);
+    }
+
+    fn on_end(&mut self, timestamp: u32) {
+        self.observer.on_end(timestamp);
+    }
+
+    fn on_event(&mut self, event: &U, timestamp: u32) {
+        self.observer.on_event(event, timestamp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamics::MAX_VELOCITY;
+    use crate::mock::{play_until, Advance, KeyEvent, MockInterface};
+    use crate::sequencer::{Action, ActionPoint};
+    use alloc::vec;
+    use opl_driver::hl::{Melody, Opl2};
+
+    type Opl = Opl2<MockInterface, Melody>;
+    type TestPlayer = SectionPlayer<Opl, 16>;
+
+    impl Advance<Opl> for TestPlayer {
+        fn now(&self) -> u64 {
+            self.position() as u64
+        }
+
+        fn to_next_event(&self) -> Option<u64> {
+            self.ticks_to_next_event().map(u64::from)
+        }
+
+        fn advance_by(&mut self, opl: &mut Opl, ticks: u64) {
+            self.advance(opl, ticks as u32).unwrap();
+        }
+    }
+
+    const CALM: usize = 0;
+    const TENSE: usize = 1;
+
+    /// A section of 8 ticks that plays a note on the channel at its start and a marker halfway
+    fn section(channel: usize, note_length: u32) -> Section<Opl, 16> {
+        Section::new(Sequence::new(&[
+            ActionPoint::new(
+                0,
+                Action::PlayNote {
+                    channel,
+                    value: Note::A(4),
+                    velocity: MAX_VELOCITY,
+                    duration: note_length,
+                },
+            ),
+            ActionPoint::new(4, Action::Marker { id: 0 }),
+            ActionPoint::new(4, Action::Marker { id: 1 }),
+        ]))
+    }
+
+    fn player(switch_point: SwitchPoint) -> TestPlayer {
+        TestPlayer::new(vec![section(0, 6), section(1, 2)], switch_point)
+    }
+
+    #[test]
+    fn sections_loop_until_a_switch_is_requested() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut player = player(SwitchPoint::Bar { length: 8 });
+
+        player.advance(&mut opl, 0).unwrap();
+        play_until(&mut player, &mut opl, &mock, 20);
+
+        assert_eq!(player.current(), CALM);
+        assert_eq!(
+            mock.key_events(),
+            vec![KeyEvent::on(0, 0), KeyEvent::off(6, 0), KeyEvent::on(8, 0), KeyEvent::off(14, 0), KeyEvent::on(16, 0)]
+        );
+    }
+
+    #[test]
+    fn switches_wait_for_the_next_bar_and_release_the_notes() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut player = player(SwitchPoint::Bar { length: 4 });
+
+        player.advance(&mut opl, 0).unwrap();
+        player.advance(&mut opl, 2).unwrap();
+        player.request(TENSE);
+        assert_eq!(player.ticks_to_next_event(), Some(2));
+
+        play_until(&mut player, &mut opl, &mock, 10);
+
+        assert_eq!(player.current(), TENSE);
+        assert_eq!(player.requested(), None);
+        assert_eq!(
+            mock.key_events(),
+            vec![KeyEvent::on(0, 0), KeyEvent::off(4, 0), KeyEvent::on(4, 1), KeyEvent::off(6, 1)]
+        );
+    }
+
+    #[test]
+    fn switches_can_wait_for_a_marker() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut player = player(SwitchPoint::Marker);
+
+        player.advance(&mut opl, 0).unwrap();
+        player.request(TENSE);
+        play_until(&mut player, &mut opl, &mock, 10);
+
+        assert_eq!(
+            mock.key_events(),
+            vec![KeyEvent::on(0, 0), KeyEvent::off(4, 0), KeyEvent::on(4, 1), KeyEvent::off(6, 1)]
+        );
+    }
+
+    #[test]
+    fn sections_can_go_on_to_the_next_one() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut player = TestPlayer::new(
+            vec![section(0, 2).then(TENSE), section(1, 2)],
+            SwitchPoint::Immediately,
+        );
+
+        player.advance(&mut opl, 0).unwrap();
+        play_until(&mut player, &mut opl, &mock, 16);
+
+        assert_eq!(player.current(), TENSE);
+        assert_eq!(
+            mock.key_events(),
+            vec![KeyEvent::on(0, 0), KeyEvent::off(2, 0), KeyEvent::on(8, 1), KeyEvent::off(10, 1), KeyEvent::on(16, 1)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "There is no section 2")]
+    fn sections_must_go_on_to_a_section_that_exists() {
+        TestPlayer::new(
+            vec![section(0, 2).then(2), section(1, 2)],
+            SwitchPoint::Immediately,
+        );
+    }
+}
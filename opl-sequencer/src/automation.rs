@@ -0,0 +1,186 @@
+//! Automation lanes: parameters of a channel that change over time.
+//!
+//! A lane either ramps a parameter from one value to another, or lets it swing around a center with an LFO.
+//! This goes further than the vibrato and tremolo bits of the OPL2, which are on or off and have the same depth for every channel.
+//! Lanes are started with [Action::Automate](crate::sequencer::Action::Automate) and update their parameter every tick.
+
+use crate::curve::Curve;
+use core::f32::consts::PI;
+
+/// What a lane changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parameter {
+    /// The output level of the modulator, from 0 (loudest) to 63.
+    /// With FM synthesis this changes the brightness of the sound, a bit like a filter.
+    ModulatorLevel,
+    /// The output level of the carrier, from 0 (loudest) to 63
+    CarrierLevel,
+    /// How much of the modulator is fed back into itself, from 0 to 7
+    Feedback,
+    /// The offset of the pitch in cents. Use it with an LFO for vibrato.
+    Pitch,
+}
+
+impl Parameter {
+    /// The amount of parameters
+    pub const COUNT: usize = 4;
+
+    pub(crate) fn index(self) -> usize {
+        match self {
+            Parameter::ModulatorLevel => 0,
+            Parameter::CarrierLevel => 1,
+            Parameter::Feedback => 2,
+            Parameter::Pitch => 3,
+        }
+    }
+
+    /// Keeps the value within what the synth can do
+    fn clamp(self, value: i32) -> i32 {
+        match self {
+            Parameter::ModulatorLevel | Parameter::CarrierLevel => value.clamp(0, 63),
+            Parameter::Feedback => value.clamp(0, 7),
+            Parameter::Pitch => value,
+        }
+    }
+}
+
+/// The shape of a low frequency oscillator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Sine,
+    Triangle,
+    /// A new random value every period, like sample and hold
+    Random,
+}
+
+/// How a lane changes its parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    /// Goes from one value to the other over the duration of the lane, and stays there
+    Ramp { from: i32, to: i32, curve: Curve },
+    /// Swings around the center by the depth, once every period in ticks.
+    /// The parameter goes back to the center at the end of the lane.
+    Lfo {
+        waveform: Waveform,
+        center: i32,
+        depth: i32,
+        period: u32,
+    },
+}
+
+/// A parameter of a channel that changes over the duration in ticks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lane {
+    pub parameter: Parameter,
+    pub shape: Shape,
+    pub duration: u32,
+}
+
+impl Lane {
+    /// The value of the parameter at the amount of ticks into the lane
+    pub fn value(&self, elapsed: u32) -> i32 {
+        let value = match self.shape {
+            Shape::Ramp { from, to, curve } => {
+                libm::roundf(curve.value(from as f32, to as f32, elapsed, self.duration)) as i32
+            }
+            Shape::Lfo { center, .. } if elapsed >= self.duration => center,
+            Shape::Lfo {
+                waveform,
+                center,
+                depth,
+                period,
+            } => {
+                let period = period.max(1);
+                let phase = (elapsed % period) as f32 / period as f32;
+                let swing = match waveform {
+                    Waveform::Sine => libm::sinf(2.0 * PI * phase),
+                    Waveform::Triangle => triangle(phase),
+                    Waveform::Random => random(elapsed / period),
+                };
+
+                center + libm::roundf(swing * depth as f32) as i32
+            }
+        };
+
+        self.parameter.clamp(value)
+    }
+}
+
+/// A triangle that starts at 0 and goes up first, like a sine
+fn triangle(phase: f32) -> f32 {
+    if phase < 0.25 {
+        4.0 * phase
+    } else if phase < 0.75 {
+        2.0 - 4.0 * phase
+    } else {
+        4.0 * phase - 4.0
+    }
+}
+
+/// A value between -1 and 1 that is the same for the same seed
+fn random(seed: u32) -> f32 {
+    // xorshift32, which doesn't work with a seed of 0
+    let mut x = seed.wrapping_add(0x9E37_79B9);
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+
+    (x as f32 / u32::MAX as f32) * 2.0 - 1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lfo(waveform: Waveform) -> Lane {
+        Lane {
+            parameter: Parameter::Pitch,
+            shape: Shape::Lfo {
+                waveform,
+                center: 0,
+                depth: 20,
+                period: 8,
+            },
+            duration: 32,
+        }
+    }
+
+    #[test]
+    fn ramps_stay_at_the_end_and_are_clamped() {
+        let lane = Lane {
+            parameter: Parameter::ModulatorLevel,
+            shape: Shape::Ramp {
+                from: 40,
+                to: 80,
+                curve: Curve::Linear,
+            },
+            duration: 10,
+        };
+
+        assert_eq!(lane.value(0), 40);
+        assert_eq!(lane.value(5), 60);
+        assert_eq!(lane.value(10), 63);
+        assert_eq!(lane.value(20), 63);
+    }
+
+    #[test]
+    fn lfos_swing_around_the_center() {
+        let sine = lfo(Waveform::Sine);
+        assert_eq!(
+            [0, 2, 4, 6, 8].map(|tick| sine.value(tick)),
+            [0, 20, 0, -20, 0]
+        );
+
+        let triangle = lfo(Waveform::Triangle);
+        assert_eq!(
+            [0, 1, 2, 4, 6].map(|tick| triangle.value(tick)),
+            [0, 10, 20, 0, -20]
+        );
+
+        // Random values are held for a period
+        let random = lfo(Waveform::Random);
+        assert!((0..8).all(|tick| random.value(tick) == random.value(0)));
+        assert!((0..32).all(|tick| random.value(tick).abs() <= 20));
+        assert_eq!(random.value(32), 0);
+    }
+}
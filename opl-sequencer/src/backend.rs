@@ -0,0 +1,225 @@
+use opl_driver::{
+    hl::Initialized, hl::Note, hl::Opl2, hl::Opl2Error, instrument::MelodyInstrument,
+    ll::HardwareInterface,
+};
+
+/// The amount of melody channels of the OPL2
+pub const CHANNELS: usize = 9;
+
+/// The register offset of the modulator of every channel. The carrier is 3 higher.
+const MODULATOR_OFFSETS: [u8; CHANNELS] = [0x00, 0x01, 0x02, 0x08, 0x09, 0x0A, 0x10, 0x11, 0x12];
+/// The key scaling/output level registers of the operators start here
+const LEVEL_REGISTERS: u8 = 0x40;
+/// The registers with the low 8 bits of the F-number of every channel
+const F_NUMBER_REGISTERS: u8 = 0xA0;
+/// The registers with the key on bit, the block and the high 2 bits of the F-number of every channel
+const KEY_ON_REGISTERS: u8 = 0xB0;
+const KEY_ON: u8 = 0x20;
+/// The registers with the feedback and the synthesis type of every channel
+const FEEDBACK_REGISTERS: u8 = 0xC0;
+
+/// The channel that the OPL2 register belongs to, or `None` for the registers of the whole chip
+pub(crate) fn register_channel(address: u8) -> Option<usize> {
+    match address {
+        // The operator registers, from the tremolo/vibrato ones at 0x20 up to the waveforms at 0xE0
+        0x20..=0x95 | 0xE0..=0xF5 => {
+            let offset = address & 0x1F;
+            MODULATOR_OFFSETS
+                .iter()
+                .position(|modulator| offset == *modulator || offset == modulator + 3)
+        }
+        0xA0..=0xA8 | 0xB0..=0xB8 | 0xC0..=0xC8 => Some((address & 0x0F) as usize),
+        _ => None,
+    }
+}
+
+/// A set of channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChannelMask(u16);
+
+impl ChannelMask {
+    pub const NONE: Self = Self(0);
+    pub const ALL: Self = Self((1 << CHANNELS) - 1);
+
+    /// The mask with only the channel, or no channels if the synth doesn't have it
+    pub const fn single(channel: usize) -> Self {
+        if channel < CHANNELS {
+            Self(1 << channel)
+        } else {
+            Self::NONE
+        }
+    }
+
+    pub fn contains(self, channel: usize) -> bool {
+        channel < CHANNELS && self.0 & (1 << channel) != 0
+    }
+
+    pub fn insert(&mut self, channel: usize) {
+        self.0 |= Self::single(channel).0;
+    }
+
+    pub fn remove(&mut self, channel: usize) {
+        self.0 &= !Self::single(channel).0;
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// The channels that are in both masks
+    pub fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    /// The channels that are in the mask, from low to high
+    pub fn channels(self) -> impl Iterator<Item = usize> {
+        (0..CHANNELS).filter(move |channel| self.contains(*channel))
+    }
+}
+
+impl From<&[usize]> for ChannelMask {
+    fn from(channels: &[usize]) -> Self {
+        let mut mask = Self::NONE;
+        for channel in channels {
+            mask.insert(*channel);
+        }
+        mask
+    }
+}
+
+/// Something that can make sound out of the actions of a [Sequence](crate::sequencer::Sequence).
+///
+/// The sequencer only talks to the synth through this trait, so the same score can drive a real OPL2,
+/// a mock, an emulator or anything else that understands notes and instruments.
+pub trait SynthBackend {
+    type Error;
+
+    /// Starts playing the note on the channel
+    fn start_note(&mut self, channel: usize, note: Note) -> Result<(), Self::Error>;
+    /// Releases the note that is playing on the channel
+    fn stop_note(&mut self, channel: usize) -> Result<(), Self::Error>;
+    /// Sets up the channel so that the next notes are played with the given instrument
+    fn set_instrument(
+        &mut self,
+        channel: usize,
+        instrument: MelodyInstrument,
+    ) -> Result<(), Self::Error>;
+    /// Writes a raw value to a register of the synth
+    fn write_register(&mut self, address: u8, value: u8) -> Result<(), Self::Error>;
+    /// Writes the key scaling/output level registers of the modulator and the carrier of the channel.
+    /// Used for velocity, see [Levels::registers](crate::dynamics::Levels::registers).
+    fn set_levels(&mut self, channel: usize, modulator: u8, carrier: u8)
+        -> Result<(), Self::Error>;
+    /// Changes the frequency of the note that is playing on the channel without starting it again.
+    /// Used for slides, see [pitch::frequency](crate::pitch::frequency).
+    /// Whether the key is on shares the register, so it has to be given as well.
+    fn set_frequency(
+        &mut self,
+        channel: usize,
+        block: u8,
+        f_number: u16,
+        key_on: bool,
+    ) -> Result<(), Self::Error>;
+    /// Changes the feedback of the modulator of the channel.
+    /// The synthesis type shares the register, so it has to be given as well.
+    fn set_feedback(
+        &mut self,
+        channel: usize,
+        feedback: u8,
+        additive: bool,
+    ) -> Result<(), Self::Error>;
+}
+
+impl<I: HardwareInterface, S: Initialized> SynthBackend for Opl2<I, S> {
+    type Error = Opl2Error;
+
+    fn start_note(&mut self, channel: usize, note: Note) -> Result<(), Self::Error> {
+        self.start_channel(channel, note)
+    }
+
+    fn stop_note(&mut self, channel: usize) -> Result<(), Self::Error> {
+        self.stop_channel(channel)
+    }
+
+    fn set_instrument(
+        &mut self,
+        channel: usize,
+        instrument: MelodyInstrument,
+    ) -> Result<(), Self::Error> {
+        self.setup_melody_instrument(channel, instrument)
+    }
+
+    fn write_register(&mut self, address: u8, value: u8) -> Result<(), Self::Error> {
+        self.ll().interface().write_register(address, &[value])?;
+        Ok(())
+    }
+
+    fn set_levels(
+        &mut self,
+        channel: usize,
+        modulator: u8,
+        carrier: u8,
+    ) -> Result<(), Self::Error> {
+        if let Some(offset) = MODULATOR_OFFSETS.get(channel) {
+            SynthBackend::write_register(self, LEVEL_REGISTERS + offset, modulator)?;
+            SynthBackend::write_register(self, LEVEL_REGISTERS + offset + 3, carrier)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_frequency(
+        &mut self,
+        channel: usize,
+        block: u8,
+        f_number: u16,
+        key_on: bool,
+    ) -> Result<(), Self::Error> {
+        if channel < CHANNELS {
+            let channel = channel as u8;
+            SynthBackend::write_register(self, F_NUMBER_REGISTERS + channel, f_number as u8)?;
+            SynthBackend::write_register(
+                self,
+                KEY_ON_REGISTERS + channel,
+                if key_on { KEY_ON } else { 0 }
+                    | (block & 0b111) << 2
+                    | (f_number >> 8) as u8 & 0b11,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn set_feedback(
+        &mut self,
+        channel: usize,
+        feedback: u8,
+        additive: bool,
+    ) -> Result<(), Self::Error> {
+        if channel < CHANNELS {
+            SynthBackend::write_register(
+                self,
+                FEEDBACK_REGISTERS + channel as u8,
+                (feedback & 0b111) << 1 | additive as u8,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_ignore_channels_the_synth_doesnt_have() {
+        assert_eq!(ChannelMask::single(CHANNELS), ChannelMask::NONE);
+        assert_eq!(ChannelMask::single(16), ChannelMask::NONE);
+
+        let mut mask = ChannelMask::from(&[0, 9, 20][..]);
+        assert_eq!(mask, ChannelMask::single(0));
+        mask.remove(64);
+        assert_eq!(mask.channels().collect::<heapless::Vec<_, CHANNELS>>(), [0]);
+    }
+}
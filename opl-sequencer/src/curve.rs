@@ -0,0 +1,57 @@
+/// The shape of a change from one value to another over a number of ticks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Curve {
+    /// The value changes by the same amount every tick
+    Linear,
+    /// The value changes by the same ratio every tick.
+    /// When one of the ends isn't positive, this falls back to [Curve::Linear].
+    Exponential,
+}
+
+impl Curve {
+    /// Gets the value after `elapsed` out of `duration` ticks
+    pub fn value(self, from: f32, to: f32, elapsed: u32, duration: u32) -> f32 {
+        if elapsed >= duration {
+            return to;
+        }
+
+        let progress = elapsed as f32 / duration as f32;
+
+        match self {
+            Curve::Exponential if from > 0.0 && to > 0.0 => {
+                from * libm::powf(to / from, progress)
+            }
+            _ => from + (to - from) * progress,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(left: f32, right: f32) {
+        assert!((left - right).abs() < 0.001, "{} != {}", left, right);
+    }
+
+    #[test]
+    fn curves_start_and_end_at_the_given_values() {
+        for curve in [Curve::Linear, Curve::Exponential].iter() {
+            assert_close(curve.value(100.0, 50.0, 0, 10), 100.0);
+            assert_close(curve.value(100.0, 50.0, 10, 10), 50.0);
+            assert_close(curve.value(100.0, 50.0, 20, 10), 50.0);
+            assert_close(curve.value(100.0, 50.0, 0, 0), 50.0);
+        }
+    }
+
+    #[test]
+    fn linear_halfway_is_the_average() {
+        assert_close(Curve::Linear.value(100.0, 50.0, 5, 10), 75.0);
+    }
+
+    #[test]
+    fn exponential_halfway_is_the_geometric_mean() {
+        assert_close(Curve::Exponential.value(100.0, 25.0, 5, 10), 50.0);
+        assert_close(Curve::Exponential.value(0.0, 10.0, 5, 10), 5.0);
+    }
+}
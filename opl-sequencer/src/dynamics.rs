@@ -0,0 +1,135 @@
+//! How loud notes are played.
+//!
+//! The OPL2 has no velocity, so it's made by turning down the output level of the operators that are heard.
+//! With FM synthesis that's only the carrier, because the modulator changes the timbre and not the volume.
+//! With additive synthesis both operators are heard, so both are turned down.
+//!
+//! Velocities go from 0 to [MAX_VELOCITY] like in MIDI. A note at [MAX_VELOCITY] sounds like the instrument itself.
+//! The volume of the whole sequence works the same way and goes from 0 to [MAX_VOLUME].
+
+/// The velocity that plays the instrument as it is
+pub const MAX_VELOCITY: u8 = 127;
+
+/// The volume that plays everything as loud as the velocity says
+pub const MAX_VOLUME: u8 = 127;
+
+/// Velocities for the dynamics of the score
+pub const PPP: u8 = 16;
+pub const PP: u8 = 33;
+pub const P: u8 = 49;
+pub const MP: u8 = 64;
+pub const MF: u8 = 80;
+pub const F: u8 = 96;
+pub const FF: u8 = 112;
+pub const FFF: u8 = MAX_VELOCITY;
+
+/// The highest output level, which is the softest
+const MAX_LEVEL: u8 = 63;
+
+/// The output levels an instrument is set up with, so velocity can be applied on top of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Levels {
+    /// The output level of the modulator, 0 is the loudest and 63 the softest
+    pub modulator: u8,
+    /// The output level of the carrier, 0 is the loudest and 63 the softest
+    pub carrier: u8,
+    /// The key scaling level bits of the modulator and the carrier, which share their register with the output level
+    pub key_scaling: (u8, u8),
+    /// True if the instrument uses additive synthesis instead of FM
+    pub additive: bool,
+}
+
+impl Levels {
+    /// The levels of an FM instrument
+    pub const fn fm(modulator: u8, carrier: u8) -> Self {
+        Self {
+            modulator,
+            carrier,
+            key_scaling: (0, 0),
+            additive: false,
+        }
+    }
+
+    /// The levels of an instrument with additive synthesis
+    pub const fn additive(modulator: u8, carrier: u8) -> Self {
+        Self {
+            modulator,
+            carrier,
+            key_scaling: (0, 0),
+            additive: true,
+        }
+    }
+
+    /// Sets the key scaling level bits of the modulator and the carrier
+    pub const fn with_key_scaling(mut self, modulator: u8, carrier: u8) -> Self {
+        self.key_scaling = (modulator, carrier);
+        self
+    }
+
+    /// The values of the key scaling/output level registers of the modulator and the carrier
+    /// for the velocity at the volume
+    pub fn registers(&self, velocity: u8, volume: u8) -> (u8, u8) {
+        let attenuation = attenuation(velocity).saturating_add(attenuation(volume));
+        let modulator = if self.additive {
+            self.modulator.saturating_add(attenuation)
+        } else {
+            self.modulator
+        };
+        let carrier = self.carrier.saturating_add(attenuation);
+
+        (
+            register(self.key_scaling.0, modulator),
+            register(self.key_scaling.1, carrier),
+        )
+    }
+}
+
+/// How many steps of 0.75 dB the output level is turned down for the velocity or the volume.
+///
+/// The volume follows the square of the velocity, which is how most MIDI synths do it.
+pub fn attenuation(velocity: u8) -> u8 {
+    if velocity == 0 {
+        return MAX_LEVEL;
+    }
+
+    let velocity = velocity.min(MAX_VELOCITY) as f32 / MAX_VELOCITY as f32;
+    let decibels = -40.0 * libm::log10f(velocity);
+    (libm::roundf(decibels / 0.75) as u8).min(MAX_LEVEL)
+}
+
+fn register(key_scaling: u8, level: u8) -> u8 {
+    (key_scaling & 0b11) << 6 | level.min(MAX_LEVEL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attenuation_follows_the_velocity() {
+        assert_eq!(attenuation(MAX_VELOCITY), 0);
+        assert_eq!(attenuation(MP), 16);
+        assert_eq!(attenuation(1), MAX_LEVEL);
+        assert_eq!(attenuation(0), MAX_LEVEL);
+        assert!(attenuation(P) > attenuation(MF));
+    }
+
+    #[test]
+    fn only_the_heard_operators_are_turned_down() {
+        assert_eq!(
+            Levels::fm(16, 0).registers(MAX_VELOCITY, MAX_VOLUME),
+            (16, 0)
+        );
+        assert_eq!(Levels::fm(16, 0).registers(MP, MAX_VOLUME), (16, 16));
+        assert_eq!(Levels::additive(16, 0).registers(MP, MAX_VOLUME), (32, 16));
+        assert_eq!(Levels::fm(16, 60).registers(MP, MAX_VOLUME), (16, 63));
+        assert_eq!(Levels::fm(16, 0).registers(MAX_VELOCITY, MP), (16, 16));
+        assert_eq!(Levels::fm(16, 0).registers(MP, MP), (16, 32));
+        assert_eq!(
+            Levels::fm(16, 0)
+                .with_key_scaling(1, 2)
+                .registers(MP, MAX_VOLUME),
+            (0x40 | 16, 0x80 | 16)
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! Tracker effects: commands that change the note on a channel every tick.
+//!
+//! In a tracker every row has an effect column next to the note. The effect runs for the ticks of the row,
+//! so a single row can play an arpeggio, fade the note or play it several times.
+//! Effects are started with [Action::Effect](crate::sequencer::Action::Effect) and work on the notes of the channel,
+//! including the ones of a [Action::PlayNote](crate::sequencer::Action::PlayNote).
+
+use crate::pitch::SEMITONE;
+
+/// What an effect does with the note on its channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// Cycles through the note, the note x semitones up and the note y semitones up, a step every tick.
+    /// The note is back at its own pitch when the effect ends.
+    Arpeggio { x: u8, y: u8 },
+    /// Changes the velocity of the note by the step every tick after the first one.
+    /// This only works on an instrument with [Levels](crate::dynamics::Levels), and the next note plays at its own velocity again.
+    VolumeSlide { step: i8 },
+    /// Starts the note again every interval in ticks
+    Retrigger { interval: u32 },
+    /// Stops the note after the ticks
+    NoteCut { ticks: u32 },
+    /// Starts the notes on the channel that start at the same time later, after the ticks.
+    /// Put the effect before the notes, so it's there when they start.
+    NoteDelay { ticks: u32 },
+}
+
+impl Effect {
+    /// The arpeggio of the tracker command `0xy`, with x in the high and y in the low nibble
+    pub const fn arpeggio(xy: u8) -> Self {
+        Effect::Arpeggio {
+            x: xy >> 4,
+            y: xy & 0xF,
+        }
+    }
+
+    /// The pitch offset in cents of the note at the amount of ticks into the effect
+    pub(crate) fn cents(&self, elapsed: u32) -> i32 {
+        match *self {
+            Effect::Arpeggio { x, y } => match elapsed % 3 {
+                0 => 0,
+                1 => x as i32 * SEMITONE,
+                _ => y as i32 * SEMITONE,
+            },
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arpeggios_cycle_through_three_notes() {
+        let arpeggio = Effect::arpeggio(0x47);
+        assert_eq!(arpeggio, Effect::Arpeggio { x: 4, y: 7 });
+        assert_eq!(
+            [0, 1, 2, 3, 4].map(|tick| arpeggio.cents(tick)),
+            [0, 400, 700, 0, 400]
+        );
+        assert_eq!(Effect::NoteCut { ticks: 2 }.cents(1), 0);
+    }
+}
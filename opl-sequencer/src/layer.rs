@@ -0,0 +1,432 @@
+//! Sound effects on top of the music.
+//!
+//! The music is played on a [Layered] backend instead of directly on the synth.
+//! When a sound effect starts, the [SfxLayer] borrows its channel from the music:
+//! the music keeps running, but what it does on that channel is only remembered and not played.
+//! When the effect is done, the channel is given back with the instrument and the note the music has on it.
+//!
+//! Effects are timed in milliseconds instead of ticks, so they sound the same at every tempo.
+
+use crate::backend::{register_channel, ChannelMask, SynthBackend, CHANNELS};
+use crate::sequencer::{Sequence, SequenceError};
+use opl_driver::hl::Note;
+use opl_driver::instrument::MelodyInstrument;
+
+/// A backend for the music that leaves alone the channels that are borrowed by sound effects
+pub struct Layered<B: SynthBackend> {
+    inner: B,
+    borrowed: ChannelMask,
+    /// The instrument the music has set on every channel
+    instruments: [Option<MelodyInstrument>; CHANNELS],
+    /// The note the music is playing on every channel
+    notes: [Option<Note>; CHANNELS],
+    /// The levels the music has set on every channel for velocity, if it changed them
+    levels: [Option<(u8, u8)>; CHANNELS],
+}
+
+impl<B: SynthBackend> Layered<B> {
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            borrowed: ChannelMask::NONE,
+            instruments: Default::default(),
+            notes: [None; CHANNELS],
+            levels: [None; CHANNELS],
+        }
+    }
+
+    /// The synth itself, for the sound effects
+    pub fn inner(&mut self) -> &mut B {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    /// The channels that are taken away from the music
+    pub fn borrowed(&self) -> ChannelMask {
+        self.borrowed
+    }
+
+    /// Takes the channel away from the music and releases the note the music had on it
+    pub fn borrow_channel(&mut self, channel: usize) -> Result<(), B::Error> {
+        if !self.borrowed.contains(channel) {
+            self.borrowed.insert(channel);
+
+            if self.notes[channel].is_some() {
+                self.inner.stop_note(channel)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Gives the channel back to the music.
+    /// The instrument and the levels of the music are set up again and the note it's playing is started.
+    pub fn return_channel(&mut self, channel: usize) -> Result<(), B::Error> {
+        if self.borrowed.contains(channel) {
+            self.borrowed.remove(channel);
+
+            if let Some(instrument) = &self.instruments[channel] {
+                self.inner.set_instrument(channel, instrument.clone())?;
+            }
+
+            if let Some((modulator, carrier)) = self.levels[channel] {
+                self.inner.set_levels(channel, modulator, carrier)?;
+            }
+
+            if let Some(note) = self.notes[channel] {
+                self.inner.start_note(channel, note)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<B: SynthBackend> SynthBackend for Layered<B> {
+    type Error = B::Error;
+
+    fn start_note(&mut self, channel: usize, note: Note) -> Result<(), Self::Error> {
+        if let Some(playing) = self.notes.get_mut(channel) {
+            *playing = Some(note);
+        }
+
+        if self.borrowed.contains(channel) {
+            return Ok(());
+        }
+
+        self.inner.start_note(channel, note)
+    }
+
+    fn stop_note(&mut self, channel: usize) -> Result<(), Self::Error> {
+        if let Some(playing) = self.notes.get_mut(channel) {
+            *playing = None;
+        }
+
+        if self.borrowed.contains(channel) {
+            return Ok(());
+        }
+
+        self.inner.stop_note(channel)
+    }
+
+    fn set_instrument(
+        &mut self,
+        channel: usize,
+        instrument: MelodyInstrument,
+    ) -> Result<(), Self::Error> {
+        // A new instrument comes with its own levels
+        if let Some(levels) = self.levels.get_mut(channel) {
+            *levels = None;
+        }
+
+        if self.borrowed.contains(channel) {
+            if let Some(music_instrument) = self.instruments.get_mut(channel) {
+                *music_instrument = Some(instrument);
+            }
+
+            return Ok(());
+        }
+
+        if let Some(music_instrument) = self.instruments.get_mut(channel) {
+            *music_instrument = Some(instrument.clone());
+        }
+
+        self.inner.set_instrument(channel, instrument)
+    }
+
+    fn set_levels(&mut self, channel: usize, modulator: u8, carrier: u8) -> Result<(), Self::Error> {
+        if let Some(levels) = self.levels.get_mut(channel) {
+            *levels = Some((modulator, carrier));
+        }
+
+        if self.borrowed.contains(channel) {
+            return Ok(());
+        }
+
+        self.inner.set_levels(channel, modulator, carrier)
+    }
+
+    fn set_frequency(
+        &mut self,
+        channel: usize,
+        block: u8,
+        f_number: u16,
+        key_on: bool,
+    ) -> Result<(), Self::Error> {
+        // The note of the music is started again at its own pitch when the channel is given back
+        if self.borrowed.contains(channel) {
+            return Ok(());
+        }
+
+        self.inner.set_frequency(channel, block, f_number, key_on)
+    }
+
+    fn set_feedback(&mut self, channel: usize, feedback: u8, additive: bool) -> Result<(), Self::Error> {
+        // The feedback of the music comes back with its instrument when the channel is given back
+        if self.borrowed.contains(channel) {
+            return Ok(());
+        }
+
+        self.inner.set_feedback(channel, feedback, additive)
+    }
+
+    fn write_register(&mut self, address: u8, value: u8) -> Result<(), Self::Error> {
+        // Raw writes aren't remembered like the instrument and the note, so the music loses them on a borrowed channel
+        if let Some(channel) = register_channel(address) {
+            if self.borrowed.contains(channel) {
+                return Ok(());
+            }
+        }
+
+        self.inner.write_register(address, value)
+    }
+}
+
+struct Effect<B: SynthBackend, const N: usize> {
+    channel: usize,
+    sequence: Sequence<B, N>,
+}
+
+/// Plays sound effects, each on a channel that is borrowed from the music for as long as the effect lasts.
+///
+/// The layer keeps its own time in milliseconds, so it only has to be told how many milliseconds went by.
+pub struct SfxLayer<B: SynthBackend, const N: usize> {
+    effects: heapless::Vec<Effect<B, N>, CHANNELS>,
+    position: u32,
+}
+
+impl<B: SynthBackend, const N: usize> SfxLayer<B, N> {
+    pub fn new() -> Self {
+        Self {
+            effects: heapless::Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Returns true if there's an effect playing
+    pub fn is_playing(&self) -> bool {
+        !self.effects.is_empty()
+    }
+
+    /// The time of the layer in milliseconds
+    pub fn position(&self) -> u32 {
+        self.position
+    }
+
+    /// Starts the effect on the channel with the instrument. An effect that's already playing on it is cut off.
+    ///
+    /// The timestamps of the effect are in milliseconds and all of its actions should use the given channel.
+    /// The effect starts on the next [Self::advance].
+    pub fn play(
+        &mut self,
+        layered: &mut Layered<B>,
+        channel: usize,
+        instrument: MelodyInstrument,
+        mut effect: Sequence<B, N>,
+    ) -> Result<(), SequenceError<B::Error>> {
+        assert!(channel < CHANNELS, "There is no channel {}", channel);
+
+        if let Some(index) = self.effects.iter().position(|e| e.channel == channel) {
+            let old = self.effects.swap_remove(index);
+            stop_sounding_notes(layered, &old)?;
+        }
+
+        layered
+            .borrow_channel(channel)
+            .map_err(SequenceError::Backend)?;
+        layered
+            .inner()
+            .set_instrument(channel, instrument)
+            .map_err(SequenceError::Backend)?;
+
+        effect.start_at(self.position);
+        // There is room for an effect on every channel and there's only one per channel
+        let _ = self.effects.push(Effect {
+            channel,
+            sequence: effect,
+        });
+
+        Ok(())
+    }
+
+    /// Moves the time forward by the amount of milliseconds and runs the effects.
+    /// The effects that are done give their channel back to the music.
+    ///
+    /// Returns false when there are no effects playing anymore.
+    pub fn advance(
+        &mut self,
+        layered: &mut Layered<B>,
+        ms: u32,
+    ) -> Result<bool, SequenceError<B::Error>> {
+        self.position = self.position.wrapping_add(ms);
+
+        let mut index = 0;
+        while index < self.effects.len() {
+            let effect = &mut self.effects[index];
+
+            if effect.sequence.run(layered.inner(), self.position)? {
+                index += 1;
+            } else {
+                let effect = self.effects.swap_remove(index);
+                stop_sounding_notes(layered, &effect)?;
+                layered
+                    .return_channel(effect.channel)
+                    .map_err(SequenceError::Backend)?;
+            }
+        }
+
+        Ok(self.is_playing())
+    }
+}
+
+impl<B: SynthBackend, const N: usize> Default for SfxLayer<B, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Releases the notes an effect that is cut off leaves behind
+fn stop_sounding_notes<B: SynthBackend, const N: usize>(
+    layered: &mut Layered<B>,
+    effect: &Effect<B, N>,
+) -> Result<(), SequenceError<B::Error>> {
+    for (channel, _) in effect.sequence.sounding_notes() {
+        layered
+            .inner()
+            .stop_note(channel)
+            .map_err(SequenceError::Backend)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamics::MAX_VELOCITY;
+    use crate::mission_impossible::{bass_instrument, motiv_instrument};
+    use crate::mock::{KeyEvent, MockInterface};
+    use crate::sequencer::{Action, ActionPoint};
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use opl_driver::hl::{Melody, Opl2};
+
+    type Opl = Opl2<MockInterface, Melody>;
+
+    fn play_note<B: SynthBackend>(channel: usize, duration: u32) -> Action<B> {
+        Action::PlayNote {
+            channel,
+            value: Note::A(4),
+            velocity: MAX_VELOCITY,
+            duration,
+        }
+    }
+
+    #[test]
+    fn effects_borrow_the_channel_and_give_it_back() {
+        let (opl, mock) = MockInterface::opl();
+        let mut layered = Layered::new(opl);
+
+        // The music holds a long note on channel 0 and plays a short one on channel 1
+        let mut music = Sequence::<Layered<Opl>, 8>::new(&[
+            ActionPoint::new(0, play_note(0, 100)),
+            ActionPoint::new(20, play_note(1, 10)),
+        ]);
+
+        let mut sfx = SfxLayer::<Opl, 8>::new();
+        music.run(&mut layered, 0).unwrap();
+
+        sfx.advance(&mut layered, 10).unwrap();
+        mock.set_tick(10);
+        sfx.play(
+            &mut layered,
+            0,
+            bass_instrument(),
+            Sequence::new(&[ActionPoint::new(0, play_note(0, 5))]),
+        )
+        .unwrap();
+        sfx.advance(&mut layered, 0).unwrap();
+
+        for time in 11..=20 {
+            mock.set_tick(time);
+            music.run(&mut layered, time).unwrap();
+            sfx.advance(&mut layered, 1).unwrap();
+        }
+
+        assert!(!sfx.is_playing());
+        assert!(layered.borrowed().is_empty());
+        assert_eq!(
+            mock.key_events(),
+            vec![
+                KeyEvent::on(0, 0),
+                // The effect cuts off the music and plays its own note
+                KeyEvent::off(10, 0),
+                KeyEvent::on(10, 0),
+                KeyEvent::off(15, 0),
+                // The music continues
+                KeyEvent::on(15, 0),
+                KeyEvent::on(20, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_music_instrument_is_restored() {
+        let (opl, mock) = MockInterface::opl();
+        let mut layered = Layered::new(opl);
+        let mut sfx = SfxLayer::<Opl, 8>::new();
+
+        sfx.play(
+            &mut layered,
+            2,
+            bass_instrument(),
+            Sequence::new(&[ActionPoint::new(0, play_note(2, 5))]),
+        )
+        .unwrap();
+        sfx.advance(&mut layered, 0).unwrap();
+
+        // The music changes the instrument and starts a note while the effect plays
+        mock.clear();
+        layered.set_instrument(2, motiv_instrument()).unwrap();
+        layered.start_note(2, Note::A(4)).unwrap();
+        layered.set_frequency(2, 4, 600, true).unwrap();
+        layered.write_register(0x42, 0).unwrap();
+        assert!(mock.writes().is_empty());
+
+        mock.set_tick(5);
+        sfx.advance(&mut layered, 5).unwrap();
+        assert!(!sfx.is_playing());
+        assert!(layered.borrowed().is_empty());
+
+        // The instrument of the music is written like it would have been on a channel that isn't borrowed
+        let (mut reference, reference_mock) = MockInterface::opl();
+        reference.set_instrument(2, motiv_instrument()).unwrap();
+        let instrument: Vec<_> = reference_mock.writes().iter().map(|write| (write.address, write.value)).collect();
+        let writes: Vec<_> = mock.writes().iter().map(|write| (write.address, write.value)).collect();
+        assert!(!instrument.is_empty());
+        assert!(writes.windows(instrument.len()).any(|window| window == &instrument[..]));
+
+        // And the note of the music is started again
+        assert_eq!(mock.key_events(), vec![KeyEvent::on(5, 2)]);
+    }
+
+    #[test]
+    fn raw_writes_to_a_borrowed_channel_are_left_out() {
+        let (opl, mock) = MockInterface::opl();
+        let mut layered = Layered::new(opl);
+        layered.borrow_channel(4).unwrap();
+
+        // The carrier level and the F-number of channel 4, and then the same for channel 5
+        for address in [0x4C, 0xA4, 0x4D, 0xA5] {
+            layered.write_register(address, 1).unwrap();
+        }
+        // Registers of the whole chip always go through
+        layered.write_register(0xBD, 1).unwrap();
+
+        let addresses: Vec<_> = mock.writes().iter().map(|write| write.address).collect();
+        assert_eq!(addresses, vec![0x4D, 0xA5, 0xBD]);
+    }
+}
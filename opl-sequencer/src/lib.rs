@@ -0,0 +1,34 @@
+//! The music side of the project: the sequencer and the songs.
+//!
+//! This crate doesn't know anything about the board, so it builds for `thumbv7em-none-eabihf` as well as for the host.
+//! To run the tests on the host, override the default target of the workspace:
+//! `cargo test -p opl-sequencer --target <host triple>`
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod adaptive;
+pub mod automation;
+pub mod backend;
+pub mod curve;
+pub mod dynamics;
+pub mod effects;
+pub mod layer;
+pub mod mission_impossible;
+pub mod mixer;
+#[cfg(test)]
+mod mock;
+pub mod observer;
+pub mod pitch;
+pub mod player;
+mod queue;
+pub mod sequencer;
+pub mod sound_effects;
+pub mod timing;
+
+pub const FULL: u32 = 128;
+pub const HALF: u32 = 64;
+pub const QUARTER: u32 = 32;
+pub const EIGHTH: u32 = 16;
+pub const SIXTEENTH: u32 = 8;
@@ -0,0 +1,548 @@
+use crate::backend::SynthBackend;
+use crate::curve::Curve;
+use crate::dynamics::{Levels, FFF};
+use crate::sequencer::{ActionPoint, Action, Instrument, Pattern, Sequence};
+use crate::{QUARTER, EIGHTH, SIXTEENTH, FULL};
+use opl_driver::hl::Note;
+use opl_driver::instrument::{MelodyInstrument, OperatorSettings};
+use opl_driver::ll::registers::{operator_settings0, operator_settings1, operator_settings2, operator_settings3, operator_settings4, channel_settings2};
+use opl_driver::ll::{ModulatorFrequencyMultiple, Bit, WaveformType, SynthesisType, ScalingLevel};
+
+pub const BPM: u32 = 178;
+
+/// The ids of the markers at the start of the parts of the song
+pub const INTRO: u32 = 0;
+pub const MAIN_MOTIV: u32 = 1;
+pub const ALT_MOTIV: u32 = 2;
+pub const FINISHER: u32 = 3;
+
+const BASS: usize = 0;
+const MELODY: usize = 1;
+const CHORD0: usize = 2;
+const CHORD1: usize = 3;
+const CHORD2: usize = 4;
+
+/// The indices of the instruments in the instrument table of the song
+const BASS_INSTRUMENT: usize = 0;
+const MOTIV_INSTRUMENT: usize = 1;
+const CHORD_FILL_INSTRUMENT: usize = 2;
+
+/// The output levels of the instruments, so the notes can be played with a velocity
+const BASS_LEVELS: Levels = Levels::fm(16, 0);
+const MOTIV_LEVELS: Levels = Levels::fm(21, 0);
+const CHORD_FILL_LEVELS: Levels = Levels::fm(8, 0);
+
+/// The full arrangement
+pub fn song<B: SynthBackend, const N: usize>() -> Sequence<B, N> {
+    #[rustfmt::skip]
+    let sequence = Sequence::new(&[
+        ActionPoint::new(0, Action::SetTempo { bpm: BPM }),
+        ActionPoint::new(0, Action::SetInstrument { channel: BASS, instrument: BASS_INSTRUMENT }),
+        ActionPoint::new(0, Action::SetInstrument { channel: MELODY, instrument: MOTIV_INSTRUMENT }),
+        ActionPoint::new(0, Action::SetInstrument { channel: CHORD0, instrument: CHORD_FILL_INSTRUMENT }),
+        ActionPoint::new(0, Action::SetInstrument { channel: CHORD1, instrument: CHORD_FILL_INSTRUMENT }),
+        ActionPoint::new(0, Action::SetInstrument { channel: CHORD2, instrument: CHORD_FILL_INSTRUMENT }),
+
+        ActionPoint::new(QUARTER     , Action::Marker { id: INTRO }),
+        ActionPoint::new(0           , bass_loop(6, BASS, 2)),
+        ActionPoint::new(0           , bass_loop(2, MELODY, 4)),
+        ActionPoint::new(QUARTER * 20, Action::Marker { id: MAIN_MOTIV }),
+        ActionPoint::new(0           , main_motiv(MELODY)),
+        ActionPoint::new(QUARTER * 20, chord_fill([CHORD0,CHORD1,CHORD2])),
+        ActionPoint::new(QUARTER * 10, Action::Marker { id: ALT_MOTIV }),
+        ActionPoint::new(0           , alt_motiv(MELODY)),
+        ActionPoint::new(QUARTER * 10, bass_loop_to_alt_transition(BASS, 2)),
+        ActionPoint::new(QUARTER * 10, main_motiv_low(MELODY)),
+        ActionPoint::new(0           , main_motiv_low(CHORD0)),
+        ActionPoint::new(0           , bass_loop_alt(BASS, 2)),
+        ActionPoint::new(QUARTER * 20, bass_loop(1, BASS, 2)),
+        ActionPoint::new(0           , alt_motiv_no_delay(MELODY)),
+        ActionPoint::new(QUARTER * 10, Action::SetInstrument { channel: CHORD0, instrument: MOTIV_INSTRUMENT }),
+        ActionPoint::new(0           , bass_finisher(BASS, CHORD0, 2, 3)),
+        ActionPoint::new(QUARTER * 5 , Action::Marker { id: FINISHER }),
+        ActionPoint::new(0           , motiv_finisher([MELODY, CHORD1, CHORD2], [4, 3, 3])),
+        // Slow down towards the last chord of the finisher
+        ActionPoint::new(QUARTER * 5 + EIGHTH, Action::TempoRamp { from_bpm: BPM, to_bpm: BPM * 3 / 4, duration: QUARTER * 3 + EIGHTH, curve: Curve::Exponential }),
+        // And fade out the last chord instead of just stopping
+        ActionPoint::new(QUARTER * 5, Action::FadeOut { ticks: QUARTER * 2 }),
+    ]);
+
+    sequence.with_instruments(&[
+        Instrument::new(bass_instrument()).with_levels(BASS_LEVELS),
+        Instrument::new(motiv_instrument()).with_levels(MOTIV_LEVELS),
+        Instrument::new(chord_fill_instrument()).with_levels(CHORD_FILL_LEVELS),
+    ])
+}
+
+pub fn bass_instrument() -> MelodyInstrument {
+    MelodyInstrument::new(
+        OperatorSettings::new(
+            operator_settings0::W::zero()
+                .modulator_frequency_multiple(ModulatorFrequencyMultiple::AtSpecified)
+                .sustain(Bit::Cleared)
+                .tremolo(Bit::Cleared)
+                .keyboard_scaling_rate(Bit::Cleared)
+                .vibrato(Bit::Cleared),
+            operator_settings1::W::zero()
+                .output_level(BASS_LEVELS.modulator)
+                .level_key_scaling(ScalingLevel::NoChange),
+            operator_settings2::W::zero()
+                .attack_rate(15)
+                .decay_rate(3),
+            operator_settings3::W::zero()
+                .sustain_level(4)
+                .release_rate(6),
+            operator_settings4::W::zero()
+                .waveform(WaveformType::Sine),
+        ),
+        channel_settings2::W::zero()
+            .feedback(5)
+            .synthesis_type(SynthesisType::FrequencyModulation),
+        OperatorSettings::new(
+            operator_settings0::W::zero()
+                .modulator_frequency_multiple(ModulatorFrequencyMultiple::AtSpecified)
+                .sustain(Bit::Cleared)
+                .tremolo(Bit::Cleared)
+                .keyboard_scaling_rate(Bit::Cleared)
+                .vibrato(Bit::Cleared),
+            operator_settings1::W::zero()
+                .output_level(BASS_LEVELS.carrier)
+                .level_key_scaling(ScalingLevel::NoChange),
+            operator_settings2::W::zero()
+                .attack_rate(9)
+                .decay_rate(4),
+            operator_settings3::W::zero()
+                .sustain_level(3)
+                .release_rate(13),
+            operator_settings4::W::zero()
+                .waveform(WaveformType::PulseSine),
+        ),
+    )
+}
+
+pub fn motiv_instrument() -> MelodyInstrument {
+    MelodyInstrument::new(
+        OperatorSettings::new(
+            operator_settings0::W::zero()
+                .modulator_frequency_multiple(ModulatorFrequencyMultiple::OneOctaveBelow)
+                .sustain(Bit::Set)
+                .tremolo(Bit::Set)
+                .keyboard_scaling_rate(Bit::Cleared)
+                .vibrato(Bit::Set),
+            operator_settings1::W::zero()
+                .output_level(MOTIV_LEVELS.modulator)
+                .level_key_scaling(ScalingLevel::NoChange),
+            operator_settings2::W::zero()
+                .attack_rate(6)
+                .decay_rate(1),
+            operator_settings3::W::zero()
+                .sustain_level(6)
+                .release_rate(6),
+            operator_settings4::W::zero()
+                .waveform(WaveformType::Sine),
+        ),
+        channel_settings2::W::zero()
+            .feedback(0)
+            .synthesis_type(SynthesisType::FrequencyModulation),
+        OperatorSettings::new(
+            operator_settings0::W::zero()
+                .modulator_frequency_multiple(ModulatorFrequencyMultiple::AtSpecified)
+                .sustain(Bit::Set)
+                .tremolo(Bit::Set)
+                .keyboard_scaling_rate(Bit::Cleared)
+                .vibrato(Bit::Set),
+            operator_settings1::W::zero()
+                .output_level(MOTIV_LEVELS.carrier)
+                .level_key_scaling(ScalingLevel::NoChange),
+            operator_settings2::W::zero()
+                .attack_rate(15)
+                .decay_rate(5),
+            operator_settings3::W::zero()
+                .sustain_level(2)
+                .release_rate(5),
+            operator_settings4::W::zero()
+                .waveform(WaveformType::Sine),
+        ),
+    )
+}
+
+pub fn chord_fill_instrument() -> MelodyInstrument {
+    MelodyInstrument::new(
+        OperatorSettings::new(
+            operator_settings0::W::zero()
+                .modulator_frequency_multiple(ModulatorFrequencyMultiple::AtSpecified)
+                .sustain(Bit::Set)
+                .tremolo(Bit::Cleared)
+                .keyboard_scaling_rate(Bit::Cleared)
+                .vibrato(Bit::Cleared),
+            operator_settings1::W::zero()
+                .output_level(CHORD_FILL_LEVELS.modulator)
+                .level_key_scaling(ScalingLevel::NoChange),
+            operator_settings2::W::zero()
+                .attack_rate(6)
+                .decay_rate(2),
+            operator_settings3::W::zero()
+                .sustain_level(3)
+                .release_rate(4),
+            operator_settings4::W::zero()
+                .waveform(WaveformType::HalfSine),
+        ),
+        channel_settings2::W::zero()
+            .feedback(1)
+            .synthesis_type(SynthesisType::FrequencyModulation),
+        OperatorSettings::new(
+            operator_settings0::W::zero()
+                .modulator_frequency_multiple(ModulatorFrequencyMultiple::AtSpecified)
+                .sustain(Bit::Set)
+                .tremolo(Bit::Cleared)
+                .keyboard_scaling_rate(Bit::Cleared)
+                .vibrato(Bit::Set),
+            operator_settings1::W::zero()
+                .output_level(CHORD_FILL_LEVELS.carrier)
+                .level_key_scaling(ScalingLevel::NoChange),
+            operator_settings2::W::zero()
+                .attack_rate(15)
+                .decay_rate(4),
+            operator_settings3::W::zero()
+                .sustain_level(1)
+                .release_rate(6),
+            operator_settings4::W::zero()
+                .waveform(WaveformType::HalfSine),
+        ),
+    )
+}
+
+
+pub fn bass_loop<B: SynthBackend>(times: u32, channel: usize, octave: u8) -> Action<B> {
+    #[rustfmt::skip]
+        let bass_sequence = Pattern::new(&[
+        ActionPoint::new(0, Action::PlayNote { channel, value: Note::G(octave), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel, value: Note::G(octave), velocity: FFF, duration: QUARTER + SIXTEENTH }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel, value: Note::Bb(octave), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(QUARTER, Action::PlayNote { channel, value: Note::C(octave+1), velocity: FFF, duration: QUARTER - 1 }),
+
+        ActionPoint::new(QUARTER, Action::PlayNote { channel, value: Note::G(octave), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel, value: Note::G(octave), velocity: FFF, duration: QUARTER + SIXTEENTH }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel, value: Note::F(octave), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(QUARTER, Action::PlayNote { channel, value: Note::Fs(octave), velocity: FFF, duration: QUARTER - 1 }),
+    ]);
+
+    Action::Repetition {
+        pattern: bass_sequence,
+        repetition_duration: QUARTER * 10,
+        repetition_times: times,
+    }
+}
+
+pub fn bass_loop_to_alt_transition<B: SynthBackend>(channel: usize, octave: u8) -> Action<B> {
+    #[rustfmt::skip]
+        let bass_sequence = Pattern::new(&[
+        ActionPoint::new(0, Action::PlayNote { channel, value: Note::G(octave), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel, value: Note::G(octave), velocity: FFF, duration: QUARTER + SIXTEENTH }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel, value: Note::Bb(octave), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(QUARTER, Action::PlayNote { channel, value: Note::C(octave+1), velocity: FFF, duration: QUARTER - 1 }),
+
+        ActionPoint::new(QUARTER, Action::PlayNote { channel, value: Note::G(octave), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel, value: Note::G(octave), velocity: FFF, duration: QUARTER + SIXTEENTH }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel, value: Note::Bb(octave), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(QUARTER, Action::PlayNote { channel, value: Note::B(octave), velocity: FFF, duration: QUARTER - 1 }),
+    ]);
+
+    Action::Repetition {
+        pattern: bass_sequence,
+        repetition_duration: QUARTER * 10,
+        repetition_times: 1,
+    }
+}
+
+pub fn bass_loop_alt<B: SynthBackend>(channel: usize, octave: u8) -> Action<B> {
+    #[rustfmt::skip]
+        let bass_sequence = Pattern::new(&[
+        ActionPoint::new(0, Action::PlayNote { channel, value: Note::C(octave), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel, value: Note::G(octave), velocity: FFF, duration: QUARTER + SIXTEENTH }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel, value: Note::Fs(octave), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(QUARTER, Action::PlayNote { channel, value: Note::G(octave), velocity: FFF, duration: QUARTER - 1 }),
+
+        ActionPoint::new(QUARTER, Action::PlayNote { channel, value: Note::C(octave), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel, value: Note::G(octave), velocity: FFF, duration: QUARTER + SIXTEENTH }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel, value: Note::F(octave), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(QUARTER, Action::PlayNote { channel, value: Note::G(octave), velocity: FFF, duration: QUARTER - 1 }),
+    
+        ActionPoint::new(QUARTER, Action::PlayNote { channel, value: Note::C(octave), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel, value: Note::G(octave), velocity: FFF, duration: QUARTER + SIXTEENTH }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel, value: Note::Eb(octave), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(QUARTER, Action::PlayNote { channel, value: Note::G(octave), velocity: FFF, duration: QUARTER - 1 }),
+    
+        ActionPoint::new(QUARTER, Action::PlayNote { channel, value: Note::C(octave), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel, value: Note::C(octave), velocity: FFF, duration: QUARTER + SIXTEENTH }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel, value: Note::Eb(octave), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(QUARTER, Action::PlayNote { channel, value: Note::F(octave), velocity: FFF, duration: QUARTER - 1 }),
+    ]);
+
+    Action::Repetition {
+        pattern: bass_sequence,
+        repetition_duration: QUARTER * 20,
+        repetition_times: 1,
+    }
+}
+
+pub fn bass_finisher<B: SynthBackend>(channel_low: usize, channel_high: usize, octave_low: u8, octave_high: u8) -> Action<B> {
+    #[rustfmt::skip]
+        let bass_sequence = Pattern::new(&[
+        ActionPoint::new(0, Action::PlayNote { channel: channel_low, value: Note::G(octave_low), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel: channel_low, value: Note::G(octave_low), velocity: FFF, duration: QUARTER + SIXTEENTH }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel: channel_low, value: Note::Bb(octave_low), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(QUARTER, Action::PlayNote { channel: channel_low, value: Note::C(octave_low+1), velocity: FFF, duration: QUARTER - 1 }),
+
+        ActionPoint::new(QUARTER, Action::PlayNote { channel: channel_low, value: Note::D(octave_low), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(0      , Action::PlayNote { channel: channel_high, value: Note::A(octave_high), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel: channel_low, value: Note::D(octave_low), velocity: FFF, duration: QUARTER + SIXTEENTH }),
+        ActionPoint::new(0               , Action::PlayNote { channel: channel_high, value: Note::A(octave_high), velocity: FFF, duration: QUARTER + SIXTEENTH }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel: channel_low, value: Note::Eb(octave_low), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(0               , Action::PlayNote { channel: channel_high, value: Note::Bb(octave_high), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(QUARTER, Action::PlayNote { channel: channel_low, value: Note::F(octave_low), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(0      , Action::PlayNote { channel: channel_high, value: Note::C(octave_high+1), velocity: FFF, duration: QUARTER - 1 }),
+    
+        ActionPoint::new(QUARTER, Action::PlayNote { channel: channel_low, value: Note::D(octave_low), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(0      , Action::PlayNote { channel: channel_high, value: Note::A(octave_high), velocity: FFF, duration: QUARTER }),
+
+        ActionPoint::new(QUARTER * 3, Action::PlayNote { channel: channel_high, value: Note::Ab(octave_high), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel: channel_high, value: Note::Cs(octave_high), velocity: FFF, duration: FULL }),
+    ]);
+
+    Action::Repetition {
+        pattern: bass_sequence,
+        repetition_duration: QUARTER * 20,
+        repetition_times: 1,
+    }
+}
+
+pub fn main_motiv<B: SynthBackend>(channel: usize) -> Action<B> {
+    const OCTAVE: u8 = 5;
+
+    #[rustfmt::skip]
+        let bass_sequence = Pattern::new(&[
+        ActionPoint::new(0, Action::PlayNote { channel, value: Note::Bb(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::G(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::D(OCTAVE), velocity: FFF, duration: FULL }),
+
+        ActionPoint::new(FULL, Action::PlayNote { channel, value: Note::Bb(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::G(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::Cs(OCTAVE), velocity: FFF, duration: FULL }),
+
+        ActionPoint::new(FULL, Action::PlayNote { channel, value: Note::Bb(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::G(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::C(OCTAVE), velocity: FFF, duration: FULL }),
+
+        ActionPoint::new(FULL, Action::PlayNote { channel, value: Note::Bb(OCTAVE - 1), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::C(OCTAVE), velocity: FFF, duration: QUARTER }),
+    ]);
+
+    Action::Repetition {
+        pattern: bass_sequence,
+        repetition_duration: QUARTER * 20,
+        repetition_times: 1,
+    }
+}
+
+pub fn main_motiv_low<B: SynthBackend>(channel: usize) -> Action<B> {
+    const OCTAVE: u8 = 5;
+
+    #[rustfmt::skip]
+        let bass_sequence = Pattern::new(&[
+        ActionPoint::new(0, Action::PlayNote { channel, value: Note::Eb(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::C(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::G(OCTAVE-1), velocity: FFF, duration: FULL }),
+
+        ActionPoint::new(FULL, Action::PlayNote { channel, value: Note::Eb(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::C(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::Fs(OCTAVE-1), velocity: FFF, duration: FULL }),
+
+        ActionPoint::new(FULL, Action::PlayNote { channel, value: Note::Eb(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::C(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::F(OCTAVE-1), velocity: FFF, duration: FULL }),
+
+        ActionPoint::new(FULL, Action::PlayNote { channel, value: Note::Eb(OCTAVE-1), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::F(OCTAVE-1), velocity: FFF, duration: QUARTER }),
+    ]);
+
+    Action::Repetition {
+        pattern: bass_sequence,
+        repetition_duration: QUARTER * 20,
+        repetition_times: 1,
+    }
+}
+
+pub fn alt_motiv<B: SynthBackend>(channel: usize) -> Action<B> {
+    const OCTAVE: u8 = 4;
+
+    #[rustfmt::skip]
+        let bass_sequence = Pattern::new(&[
+        ActionPoint::new(QUARTER, Action::PlayNote { channel, value: Note::Bb(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::G(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::Fs(OCTAVE+1), velocity: FFF, duration: QUARTER*3 }),
+
+        ActionPoint::new(FULL, Action::PlayNote { channel, value: Note::Bb(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::G(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::F(OCTAVE+1), velocity: FFF, duration: QUARTER*3 }),
+
+        ActionPoint::new(FULL, Action::PlayNote { channel, value: Note::Bb(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::G(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::E(OCTAVE+1), velocity: FFF, duration: QUARTER*3 }),
+
+        ActionPoint::new(QUARTER*3, Action::PlayNote { channel, value: Note::Eb(OCTAVE+1), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::D(OCTAVE+1), velocity: FFF, duration: QUARTER }),
+    ]);
+
+    Action::Repetition {
+        pattern: bass_sequence,
+        repetition_duration: QUARTER * 20,
+        repetition_times: 1,
+    }
+}
+
+pub fn alt_motiv_no_delay<B: SynthBackend>(channel: usize) -> Action<B> {
+    const OCTAVE: u8 = 4;
+
+    #[rustfmt::skip]
+        let bass_sequence = Pattern::new(&[
+        ActionPoint::new(0, Action::PlayNote { channel, value: Note::Bb(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::G(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::Fs(OCTAVE+1), velocity: FFF, duration: FULL }),
+
+        ActionPoint::new(FULL, Action::PlayNote { channel, value: Note::Bb(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::G(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::F(OCTAVE+1), velocity: FFF, duration: FULL }),
+
+        ActionPoint::new(FULL, Action::PlayNote { channel, value: Note::Bb(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::G(OCTAVE), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::E(OCTAVE+1), velocity: FFF, duration: FULL }),
+
+        ActionPoint::new(FULL, Action::PlayNote { channel, value: Note::Eb(OCTAVE+1), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel, value: Note::D(OCTAVE+1), velocity: FFF, duration: EIGHTH }),
+    ]);
+
+    Action::Repetition {
+        pattern: bass_sequence,
+        repetition_duration: QUARTER * 20,
+        repetition_times: 1,
+    }
+}
+
+pub fn motiv_finisher<B: SynthBackend>(channels: [usize; 3], octaves: [u8; 3]) -> Action<B> {
+    #[rustfmt::skip]
+    let bass_sequence = Pattern::new(&[
+        ActionPoint::new(0, Action::PlayNote { channel: channels[1], value: Note::Eb(octaves[1]+1), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(EIGHTH, Action::PlayNote { channel: channels[1], value: Note::D(octaves[1]+1), velocity: FFF, duration: EIGHTH }),
+
+        ActionPoint::new(EIGHTH * 2, Action::PlayNote { channel: channels[0], value: Note::G(octaves[0]), velocity: FFF, duration: QUARTER + EIGHTH }),
+        ActionPoint::new(0         , Action::PlayNote { channel: channels[1], value: Note::A(octaves[1]), velocity: FFF, duration: QUARTER + EIGHTH }),
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel: channels[0], value: Note::Ab(octaves[0]), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(0         , Action::PlayNote { channel: channels[1], value: Note::Bb(octaves[1]), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(QUARTER   , Action::PlayNote { channel: channels[0], value: Note::Bb(octaves[0]), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(0         , Action::PlayNote { channel: channels[1], value: Note::C(octaves[1]+1), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(QUARTER   , Action::PlayNote { channel: channels[0], value: Note::G(octaves[0]), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(0         , Action::PlayNote { channel: channels[1], value: Note::A(octaves[1]), velocity: FFF, duration: QUARTER }),
+
+        ActionPoint::new(QUARTER * 3, Action::PlayNote { channel: channels[0], value: Note::Bb(octaves[0]), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(0          , Action::PlayNote { channel: channels[1], value: Note::D(octaves[1]+1), velocity: FFF, duration: EIGHTH }),
+        ActionPoint::new(0          , Action::PlayNote { channel: channels[2], value: Note::F(octaves[2]+1), velocity: FFF, duration: EIGHTH }),
+
+        ActionPoint::new(EIGHTH     , Action::PlayNote { channel: channels[0], value: Note::Bb(octaves[0]), velocity: FFF, duration: FULL }),
+        ActionPoint::new(0          , Action::PlayNote { channel: channels[1], value: Note::Eb(octaves[1]+1), velocity: FFF, duration: FULL }),
+        ActionPoint::new(0          , Action::PlayNote { channel: channels[2], value: Note::G(octaves[2]+1), velocity: FFF, duration: FULL }),
+
+    ]);
+
+    Action::Repetition {
+        pattern: bass_sequence,
+        repetition_duration: QUARTER * 15,
+        repetition_times: 1,
+    }
+}
+
+pub fn chord_fill<B: SynthBackend>(channels: [usize; 3]) -> Action<B> {
+    const OCTAVE: u8 = 4;
+
+    #[rustfmt::skip]
+    let fill = Pattern::new(&[
+        ActionPoint::new(0, Action::PlayNote { channel: channels[0], value: Note::G(OCTAVE), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(0, Action::PlayNote { channel: channels[1], value: Note::D(OCTAVE), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(0, Action::PlayNote { channel: channels[2], value: Note::Bb(OCTAVE-1), velocity: FFF, duration: QUARTER }),
+        // ---
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel: channels[0], value: Note::G(OCTAVE), velocity: FFF, duration: QUARTER + SIXTEENTH }),
+        ActionPoint::new(0               , Action::PlayNote { channel: channels[1], value: Note::D(OCTAVE), velocity: FFF, duration: QUARTER + SIXTEENTH }),
+        ActionPoint::new(0               , Action::PlayNote { channel: channels[2], value: Note::Bb(OCTAVE-1), velocity: FFF, duration: QUARTER + SIXTEENTH }),
+        // ---
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel: channels[0], value: Note::Bb(OCTAVE), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(0               , Action::PlayNote { channel: channels[1], value: Note::F(OCTAVE), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(0               , Action::PlayNote { channel: channels[2], value: Note::D(OCTAVE), velocity: FFF, duration: QUARTER - 1 }),
+        // ---
+        ActionPoint::new(QUARTER, Action::PlayNote { channel: channels[0], value: Note::Eb(OCTAVE), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(0      , Action::PlayNote { channel: channels[1], value: Note::G(OCTAVE), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(0      , Action::PlayNote { channel: channels[2], value: Note::C(OCTAVE+1), velocity: FFF, duration: QUARTER - 1 }),
+
+        ActionPoint::new(QUARTER, Action::PlayNote { channel: channels[0], value: Note::G(OCTAVE), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(0      , Action::PlayNote { channel: channels[1], value: Note::D(OCTAVE), velocity: FFF, duration: QUARTER }),
+        ActionPoint::new(0      , Action::PlayNote { channel: channels[2], value: Note::Bb(OCTAVE-1), velocity: FFF, duration: QUARTER }),
+        // ---
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel: channels[0], value: Note::G(OCTAVE), velocity: FFF, duration: QUARTER + SIXTEENTH }),
+        ActionPoint::new(0               , Action::PlayNote { channel: channels[1], value: Note::D(OCTAVE), velocity: FFF, duration: QUARTER + SIXTEENTH }),
+        ActionPoint::new(0               , Action::PlayNote { channel: channels[2], value: Note::Bb(OCTAVE-1), velocity: FFF, duration: QUARTER + SIXTEENTH }),
+        // ---
+        ActionPoint::new(QUARTER + EIGHTH, Action::PlayNote { channel: channels[0], value: Note::Ab(OCTAVE-1), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(0               , Action::PlayNote { channel: channels[1], value: Note::C(OCTAVE), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(0               , Action::PlayNote { channel: channels[2], value: Note::F(OCTAVE), velocity: FFF, duration: QUARTER - 1 }),
+        // ---
+        ActionPoint::new(QUARTER, Action::PlayNote { channel: channels[0], value: Note::A(OCTAVE-1), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(0      , Action::PlayNote { channel: channels[1], value: Note::C(OCTAVE), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(0      , Action::PlayNote { channel: channels[2], value: Note::Fs(OCTAVE), velocity: FFF, duration: QUARTER - 1 }),
+        // ---
+        ActionPoint::new(QUARTER, Action::PlayNote { channel: channels[0], value: Note::Bb(OCTAVE-1), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(0      , Action::PlayNote { channel: channels[1], value: Note::D(OCTAVE), velocity: FFF, duration: QUARTER - 1 }),
+        ActionPoint::new(0      , Action::PlayNote { channel: channels[2], value: Note::G(OCTAVE), velocity: FFF, duration: QUARTER - 1 }),
+    ]);
+
+    Action::Repetition {
+        pattern: fill,
+        repetition_duration: QUARTER * 20,
+        repetition_times: 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockInterface;
+    use opl_driver::hl::{Melody, Opl2};
+
+    #[test]
+    fn song_is_valid() {
+        let sequence: Sequence<Opl2<MockInterface, Melody>, 32> = song();
+
+        assert!(sequence.validate().is_ok());
+        assert_eq!(sequence.instruments().len(), 3);
+    }
+
+    #[test]
+    fn song_plays_to_the_end() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut sequence: Sequence<Opl2<MockInterface, Melody>, 32> = song();
+
+        let mut tick = 0;
+        while sequence.run(&mut opl, tick).unwrap() {
+            tick += 1;
+            mock.set_tick(tick);
+            assert!(tick < QUARTER * 200, "The song should have ended by now");
+        }
+
+        let events = mock.key_events();
+        assert!(!events.is_empty());
+        assert!(events.iter().all(|e| e.channel <= CHORD2));
+        // Every note that was started has been stopped
+        assert_eq!(
+            events.iter().filter(|e| e.on).count(),
+            events.iter().filter(|e| !e.on).count()
+        );
+    }
+}
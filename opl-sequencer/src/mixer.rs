@@ -0,0 +1,431 @@
+//! Plays several sequences at the same time, each at its own tempo.
+//!
+//! Every [Track] has its own [TickClock], so the mixer works in timer counts instead of ticks.
+//! The tracks get their own channels, so they can't get in each other's way.
+
+use crate::backend::{ChannelMask, SynthBackend};
+use crate::sequencer::{Sequence, SequenceError};
+use crate::timing::TickClock;
+
+/// The tempo of a track that doesn't set one
+pub const DEFAULT_BPM: u32 = 120;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MixerError<E> {
+    /// The channels of the new track are already used by another track
+    Overlap { track: usize, channels: ChannelMask },
+    /// The sequence of the new track plays on a channel that isn't in the channels of the track
+    OutsideMask { channel: usize },
+    /// The new track has a tempo of 0 bpm
+    InvalidTempo,
+    /// There's no room for another track
+    Full,
+    /// There's no track with the index
+    UnknownTrack { index: usize },
+    /// The sequence of a track returned an error
+    Sequence(SequenceError<E>),
+}
+
+impl<E> From<SequenceError<E>> for MixerError<E> {
+    fn from(error: SequenceError<E>) -> Self {
+        MixerError::Sequence(error)
+    }
+}
+
+/// A sequence in the mixer with its own tempo, start time and channels
+pub struct Track<B: SynthBackend, const N: usize, U = ()> {
+    sequence: Sequence<B, N, U>,
+    channels: ChannelMask,
+    bpm: u32,
+    start: u64,
+    paused: bool,
+    /// The time the track was paused at
+    paused_at: u64,
+    /// Created when the track is added to the mixer
+    clock: Option<TickClock>,
+    /// The last tick that ran and the time it ran at
+    tick: u32,
+    tick_time: u64,
+    /// The next tick that has to run and the time it has to run at
+    next: Option<(u32, u64)>,
+}
+
+impl<B: SynthBackend, const N: usize, U: Clone> Track<B, N, U> {
+    /// A track for the sequence that may only use the given channels
+    pub fn new(sequence: Sequence<B, N, U>, channels: ChannelMask) -> Self {
+        Self {
+            sequence,
+            channels,
+            bpm: DEFAULT_BPM,
+            start: 0,
+            paused: false,
+            paused_at: 0,
+            clock: None,
+            tick: 0,
+            tick_time: 0,
+            next: None,
+        }
+    }
+
+    /// Sets the tempo the track starts at. An [Action::SetTempo](crate::sequencer::Action::SetTempo) in the sequence still changes it.
+    /// The mixer doesn't take a track with a tempo of 0 bpm.
+    pub fn bpm(mut self, bpm: u32) -> Self {
+        self.bpm = bpm;
+        self
+    }
+
+    /// Lets the track start later than the mixer, after the given amount of timer counts
+    pub fn start_at(mut self, start: u64) -> Self {
+        self.start = start;
+        self
+    }
+
+    /// Adds the track to the mixer in a paused state
+    pub fn paused(mut self) -> Self {
+        self.paused = true;
+        self
+    }
+
+    pub fn channels(&self) -> ChannelMask {
+        self.channels
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn sequence(&self) -> &Sequence<B, N, U> {
+        &self.sequence
+    }
+
+    /// Works out when the next event of the sequence has to run
+    fn schedule(&mut self) {
+        let clock = self.clock.as_mut().unwrap();
+        let tick = self.tick;
+        let tick_time = self.tick_time;
+
+        self.next = self
+            .sequence
+            .next_timestamp()
+            .map(|next_tick| (next_tick, tick_time + clock.advance(next_tick.wrapping_sub(tick))));
+    }
+}
+
+/// Runs up to `T` tracks at the same time
+pub struct Mixer<B: SynthBackend, const N: usize, const T: usize, U = ()> {
+    tracks: heapless::Vec<Track<B, N, U>, T>,
+    timer_hz: u32,
+    ticks_per_beat: u32,
+    now: u64,
+}
+
+impl<B: SynthBackend, const N: usize, const T: usize, U: Clone> Mixer<B, N, T, U> {
+    /// Creates a mixer that's advanced in counts of a timer running at `timer_hz`
+    pub fn new(timer_hz: u32, ticks_per_beat: u32) -> Self {
+        Self {
+            tracks: heapless::Vec::new(),
+            timer_hz,
+            ticks_per_beat,
+            now: 0,
+        }
+    }
+
+    /// Adds the track and returns its index.
+    /// The start time of the track is counted from now.
+    ///
+    /// The actions of the sequence are checked against the channels of the track,
+    /// except for [Action::Custom](crate::sequencer::Action::Custom)s, which can do anything.
+    pub fn add(&mut self, mut track: Track<B, N, U>) -> Result<usize, MixerError<B::Error>> {
+        if track.bpm == 0 {
+            return Err(MixerError::InvalidTempo);
+        }
+
+        let mut outside = None;
+        track.sequence.visit_actions(&mut |action| match action.channel() {
+            Some(channel) if outside.is_none() && !track.channels.contains(channel) => outside = Some(channel),
+            _ => {}
+        });
+        if let Some(channel) = outside {
+            return Err(MixerError::OutsideMask { channel });
+        }
+
+        for (index, other) in self.tracks.iter().enumerate() {
+            let overlap = track.channels.intersection(other.channels);
+            if !overlap.is_empty() {
+                return Err(MixerError::Overlap {
+                    track: index,
+                    channels: overlap,
+                });
+            }
+        }
+
+        track.clock = Some(TickClock::new(
+            self.timer_hz,
+            self.ticks_per_beat,
+            track.bpm,
+        ));
+        track.tick = 0;
+        track.tick_time = self.now + track.start;
+        track.paused_at = self.now;
+        track.schedule();
+
+        self.tracks.push(track).map_err(|_| MixerError::Full)?;
+        Ok(self.tracks.len() - 1)
+    }
+
+    /// The amount of timer counts the mixer has been advanced by
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    pub fn track(&self, index: usize) -> Option<&Track<B, N, U>> {
+        self.tracks.get(index)
+    }
+
+    /// Stops running the track and releases its notes.
+    /// The time doesn't move for the track until it's resumed.
+    pub fn pause(&mut self, backend: &mut B, index: usize) -> Result<(), MixerError<B::Error>> {
+        let track = self
+            .tracks
+            .get_mut(index)
+            .ok_or(MixerError::UnknownTrack { index })?;
+
+        if !track.paused {
+            track.paused = true;
+            track.paused_at = self.now;
+            for (channel, _) in track.sequence.sounding_notes() {
+                backend.stop_note(channel).map_err(SequenceError::Backend)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Continues the track where it was paused and starts the notes again that it was playing
+    pub fn resume(&mut self, backend: &mut B, index: usize) -> Result<(), MixerError<B::Error>> {
+        let now = self.now;
+        let track = self
+            .tracks
+            .get_mut(index)
+            .ok_or(MixerError::UnknownTrack { index })?;
+
+        if track.paused {
+            track.paused = false;
+
+            // Everything that was due while paused is moved to now
+            let paused_for = now - track.paused_at;
+            track.tick_time += paused_for;
+            if let Some((_, next_time)) = &mut track.next {
+                *next_time += paused_for;
+            }
+
+            track.sequence.start_sounding_notes(backend)?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves the time forward by the amount of timer counts and runs every track that has something to do.
+    ///
+    /// Returns false when all tracks are done or paused.
+    pub fn advance(
+        &mut self,
+        backend: &mut B,
+        counts: u64,
+    ) -> Result<bool, SequenceError<B::Error>> {
+        self.now += counts;
+
+        for track in self.tracks.iter_mut().filter(|track| !track.paused) {
+            while let Some((next_tick, next_time)) = track.next {
+                if next_time > self.now {
+                    break;
+                }
+
+                track.sequence.run(backend, next_tick)?;
+                if let Some(bpm) = track.sequence.take_tempo_change() {
+                    track.clock.as_mut().unwrap().set_bpm(bpm);
+                }
+
+                track.tick = next_tick;
+                track.tick_time = next_time;
+                track.schedule();
+            }
+        }
+
+        Ok(self
+            .tracks
+            .iter()
+            .any(|track| !track.paused && track.next.is_some()))
+    }
+
+    /// The amount of timer counts until a track has something to do.
+    /// This is `None` when no track that's playing has anything left.
+    pub fn counts_to_next_event(&self) -> Option<u64> {
+        self.tracks
+            .iter()
+            .filter(|track| !track.paused)
+            .filter_map(|track| track.next)
+            .map(|(_, next_time)| next_time.saturating_sub(self.now))
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamics::MAX_VELOCITY;
+    use crate::mock::{play_until, Advance, KeyEvent, MockInterface};
+    use crate::sequencer::{Action, ActionPoint};
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use opl_driver::hl::{Melody, Note, Opl2};
+
+    type Opl = Opl2<MockInterface, Melody>;
+    /// Timed in milliseconds with a tick per beat, so a tick at 60 bpm is 1000 counts
+    type TestMixer = Mixer<Opl, 8, 4>;
+
+    impl Advance<Opl> for TestMixer {
+        fn now(&self) -> u64 {
+            self.now()
+        }
+
+        fn to_next_event(&self) -> Option<u64> {
+            self.counts_to_next_event()
+        }
+
+        fn advance_by(&mut self, opl: &mut Opl, counts: u64) {
+            self.advance(opl, counts).unwrap();
+        }
+    }
+
+    /// Three notes of a tick on the channel
+    fn notes(channel: usize) -> Sequence<Opl, 8> {
+        let note = || Action::PlayNote {
+            channel,
+            value: Note::A(4),
+            velocity: MAX_VELOCITY,
+            duration: 1,
+        };
+
+        Sequence::new(&[
+            ActionPoint::new(0, note()),
+            ActionPoint::new(1, note()),
+            ActionPoint::new(1, note()),
+        ])
+    }
+
+    #[test]
+    fn overlapping_channels_are_an_error() {
+        let mut mixer = TestMixer::new(1000, 1);
+
+        mixer
+            .add(Track::new(notes(0), ChannelMask::from(&[0, 1][..])))
+            .unwrap();
+        assert!(matches!(
+            mixer.add(Track::new(notes(1), ChannelMask::from(&[1, 2][..]))),
+            Err(MixerError::Overlap {
+                track: 0,
+                channels
+            }) if channels == ChannelMask::single(1)
+        ));
+    }
+
+    #[test]
+    fn sequences_must_stay_in_the_channels_of_their_track() {
+        let mut mixer = TestMixer::new(1000, 1);
+
+        assert!(matches!(
+            mixer.add(Track::new(notes(0), ChannelMask::single(1))),
+            Err(MixerError::OutsideMask { channel: 0 })
+        ));
+        assert!(mixer.add(Track::new(notes(0), ChannelMask::ALL)).is_ok());
+    }
+
+    #[test]
+    fn tracks_need_a_tempo_and_an_index_that_exists() {
+        let (mut opl, _) = MockInterface::opl();
+        let mut mixer = TestMixer::new(1000, 1);
+
+        assert!(matches!(
+            mixer.add(Track::new(notes(0), ChannelMask::single(0)).bpm(0)),
+            Err(MixerError::InvalidTempo)
+        ));
+        assert!(mixer.track(0).is_none());
+        assert!(matches!(
+            mixer.pause(&mut opl, 0),
+            Err(MixerError::UnknownTrack { index: 0 })
+        ));
+        assert!(matches!(
+            mixer.resume(&mut opl, 0),
+            Err(MixerError::UnknownTrack { index: 0 })
+        ));
+    }
+
+    #[test]
+    fn tracks_run_at_their_own_tempo() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut mixer = TestMixer::new(1000, 1);
+
+        mixer
+            .add(Track::new(notes(0), ChannelMask::single(0)).bpm(60))
+            .unwrap();
+        mixer
+            .add(
+                Track::new(notes(1), ChannelMask::single(1))
+                    .bpm(120)
+                    .start_at(100),
+            )
+            .unwrap();
+
+        mixer.advance(&mut opl, 0).unwrap();
+        play_until(&mut mixer, &mut opl, &mock, 10_000);
+
+        let note_ons: Vec<_> = mock.key_events().into_iter().filter(|e| e.on).collect();
+        assert_eq!(
+            note_ons,
+            vec![
+                KeyEvent::on(0, 0),
+                KeyEvent::on(100, 1),
+                KeyEvent::on(600, 1),
+                KeyEvent::on(1000, 0),
+                KeyEvent::on(1100, 1),
+                KeyEvent::on(2000, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn paused_tracks_stand_still() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut mixer = TestMixer::new(1000, 1);
+
+        mixer
+            .add(Track::new(notes(0), ChannelMask::single(0)).bpm(60))
+            .unwrap();
+        mixer.advance(&mut opl, 0).unwrap();
+
+        play_until(&mut mixer, &mut opl, &mock, 500);
+        mixer.pause(&mut opl, 0).unwrap();
+        assert_eq!(mixer.counts_to_next_event(), None);
+        assert!(!mixer.advance(&mut opl, 0).unwrap());
+
+        play_until(&mut mixer, &mut opl, &mock, 2500);
+        mixer.resume(&mut opl, 0).unwrap();
+        play_until(&mut mixer, &mut opl, &mock, 10_000);
+
+        // The track goes on where it was paused, 2000 counts later
+        assert_eq!(
+            mock.key_events(),
+            vec![
+                KeyEvent::on(0, 0),
+                KeyEvent::off(500, 0),
+                KeyEvent::on(2500, 0),
+                KeyEvent::off(3000, 0),
+                KeyEvent::on(3000, 0),
+                KeyEvent::off(4000, 0),
+                KeyEvent::on(4000, 0),
+                KeyEvent::off(5000, 0),
+            ]
+        );
+    }
+}
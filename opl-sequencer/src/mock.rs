@@ -0,0 +1,172 @@
+//! A fake OPL2 chip for running the sequencer on the host.
+//!
+//! The [MockInterface] can be given to an [Opl2](opl_driver::hl::Opl2) instead of the real hardware.
+//! All register writes are recorded together with the tick they happened on.
+//! The interface is cheap to clone and all clones share the same recording,
+//! so a clone can be kept around to inspect the writes after the original has been moved into the driver.
+
+use alloc::{rc::Rc, vec::Vec};
+use core::cell::{Cell, RefCell};
+use opl_driver::{
+    hl::{Melody, Opl2},
+    ll::{HardwareInterface, InterfaceError, RegisterInterface},
+};
+
+/// The register that holds the key-on bit of the first channel
+const KEY_ON_REGISTER: u8 = 0xB0;
+const KEY_ON_BIT: u8 = 0x20;
+const CHANNELS: u8 = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterWrite {
+    pub tick: u32,
+    pub address: u8,
+    pub value: u8,
+}
+
+/// A note being keyed on or off, derived from the register writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub tick: u32,
+    pub channel: usize,
+    pub on: bool,
+}
+
+impl KeyEvent {
+    pub fn on(tick: u32, channel: usize) -> Self {
+        Self {
+            tick,
+            channel,
+            on: true,
+        }
+    }
+
+    pub fn off(tick: u32, channel: usize) -> Self {
+        Self {
+            tick,
+            channel,
+            on: false,
+        }
+    }
+}
+
+/// Something that runs from event to event, so it can be played with [play_until]
+pub trait Advance<B> {
+    /// The time it's at
+    fn now(&self) -> u64;
+    /// The time until it has something to do
+    fn to_next_event(&self) -> Option<u64>;
+    fn advance_by(&mut self, backend: &mut B, time: u64);
+}
+
+/// Advances from event to event and then to the time.
+/// The writes are recorded at the time of the event they belong to.
+pub fn play_until<B, A: Advance<B>>(player: &mut A, backend: &mut B, mock: &MockInterface, until: u64) {
+    while let Some(next) = player.to_next_event() {
+        let time = player.now() + next;
+        if time > until {
+            break;
+        }
+
+        mock.set_tick(time as u32);
+        player.advance_by(backend, next);
+    }
+
+    let now = player.now();
+    mock.set_tick(until as u32);
+    player.advance_by(backend, until - now);
+}
+
+#[derive(Clone, Default)]
+pub struct MockInterface {
+    tick: Rc<Cell<u32>>,
+    writes: Rc<RefCell<Vec<RegisterWrite>>>,
+}
+
+impl MockInterface {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an initialized OPL2 driver on top of a mock.
+    /// The writes done during initialization are already cleared.
+    pub fn opl() -> (Opl2<Self, Melody>, Self) {
+        let interface = Self::new();
+        let opl = Opl2::new(interface.clone()).initialize().unwrap();
+        interface.clear();
+        (opl, interface)
+    }
+
+    /// Sets the tick that is recorded with the next writes
+    pub fn set_tick(&self, tick: u32) {
+        self.tick.set(tick);
+    }
+
+    pub fn writes(&self) -> Vec<RegisterWrite> {
+        self.writes.borrow().clone()
+    }
+
+    pub fn clear(&self) {
+        self.writes.borrow_mut().clear();
+    }
+
+    /// Gets all the moments a channel was keyed on or off.
+    ///
+    /// Writes that don't change the key-on state of a channel are left out.
+    pub fn key_events(&self) -> Vec<KeyEvent> {
+        let mut key_states = [false; CHANNELS as usize];
+        let mut events = Vec::new();
+
+        for write in self.writes.borrow().iter() {
+            if !(KEY_ON_REGISTER..KEY_ON_REGISTER + CHANNELS).contains(&write.address) {
+                continue;
+            }
+
+            let channel = (write.address - KEY_ON_REGISTER) as usize;
+            let on = write.value & KEY_ON_BIT != 0;
+
+            if key_states[channel] != on {
+                key_states[channel] = on;
+                events.push(KeyEvent {
+                    tick: write.tick,
+                    channel,
+                    on,
+                });
+            }
+        }
+
+        events
+    }
+}
+
+impl RegisterInterface for MockInterface {
+    type Address = u8;
+    type InterfaceError = InterfaceError;
+
+    fn read_register(&mut self, _address: u8, value: &mut [u8]) -> Result<(), InterfaceError> {
+        // The OPL2 is write-only
+        value.iter_mut().for_each(|v| *v = 0);
+        Ok(())
+    }
+
+    fn write_register(&mut self, address: u8, value: &[u8]) -> Result<(), InterfaceError> {
+        let mut writes = self.writes.borrow_mut();
+
+        for (offset, value) in value.iter().enumerate() {
+            writes.push(RegisterWrite {
+                tick: self.tick.get(),
+                address: address + offset as u8,
+                value: *value,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl HardwareInterface for MockInterface {
+    fn reset(&mut self) -> Result<(), InterfaceError> {
+        self.writes.borrow_mut().clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,24 @@
+use opl_driver::hl::Note;
+
+/// Gets told what a [Sequence](crate::sequencer::Sequence) is doing while it runs.
+///
+/// Use this to keep things like LEDs or game logic in sync with the music.
+/// All methods do nothing by default, so only the interesting ones need to be implemented.
+/// The methods are called from wherever the sequence is run, so keep them short.
+///
+/// `U` is the type of the [Action::User](crate::sequencer::Action::User) events of the sequence.
+pub trait Observer<U = ()> {
+    /// An [Action::Marker](crate::sequencer::Action::Marker) was passed
+    fn on_marker(&mut self, _id: u32, _timestamp: u32) {}
+    /// A note was started on the channel
+    fn on_note_on(&mut self, _channel: usize, _note: Note, _timestamp: u32) {}
+    /// The note on the channel was released
+    fn on_note_off(&mut self, _channel: usize, _timestamp: u32) {}
+    /// The last event of the sequence has run
+    fn on_end(&mut self, _timestamp: u32) {}
+    /// An [Action::User](crate::sequencer::Action::User) ran with the event
+    fn on_event(&mut self, _event: &U, _timestamp: u32) {}
+}
+
+/// The observer that doesn't look
+impl<U> Observer<U> for () {}
@@ -0,0 +1,77 @@
+//! Pitches between the notes, for slides and bends.
+//!
+//! The OPL2 plays a frequency that is set with a block (the octave) and an F-number (the frequency within the octave).
+//! Pitches are counted in cents from C in octave 0, so a slide is just counting from one number to another.
+
+use opl_driver::hl::Note;
+
+/// The amount of cents in a semitone
+pub const SEMITONE: i32 = 100;
+/// The amount of cents in an octave
+pub const OCTAVE: i32 = 12 * SEMITONE;
+
+/// The highest block of the OPL2
+const MAX_BLOCK: i32 = 7;
+
+/// The F-numbers of the notes from C to B in any block, and the C of the block above
+const F_NUMBERS: [u16; 13] = [342, 363, 385, 408, 432, 458, 485, 514, 544, 577, 611, 647, 684];
+
+/// The pitch of the note in cents
+pub fn cents(note: Note) -> i32 {
+    let (semitone, octave) = match note {
+        Note::C(octave) => (0, octave),
+        Note::Cs(octave) => (1, octave),
+        Note::D(octave) => (2, octave),
+        Note::Eb(octave) => (3, octave),
+        Note::E(octave) => (4, octave),
+        Note::F(octave) => (5, octave),
+        Note::Fs(octave) => (6, octave),
+        Note::G(octave) => (7, octave),
+        Note::Ab(octave) => (8, octave),
+        Note::A(octave) => (9, octave),
+        Note::Bb(octave) => (10, octave),
+        Note::B(octave) => (11, octave),
+    };
+
+    octave as i32 * OCTAVE + semitone * SEMITONE
+}
+
+/// The block and the F-number for the pitch in cents.
+/// Pitches outside of the range of the OPL2 are clamped.
+///
+/// Between two semitones the F-number is interpolated exponentially, like the pitch goes up in cents.
+/// Only the rounding of the F-numbers is off, which is less than 3 cents at the bottom of a block.
+pub fn frequency(cents: i32) -> (u8, u16) {
+    let cents = cents.clamp(0, (MAX_BLOCK + 1) * OCTAVE - 1);
+    let block = cents / OCTAVE;
+    let semitone = (cents % OCTAVE / SEMITONE) as usize;
+    let fraction = cents % SEMITONE;
+
+    let low = F_NUMBERS[semitone] as f32;
+    let high = F_NUMBERS[semitone + 1] as f32;
+    let f_number = low * libm::powf(high / low, fraction as f32 / SEMITONE as f32);
+
+    (block as u8, libm::roundf(f_number) as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_are_counted_from_c0() {
+        assert_eq!(cents(Note::C(0)), 0);
+        assert_eq!(cents(Note::A(4)), 4 * OCTAVE + 900);
+        assert_eq!(cents(Note::C(5)) - cents(Note::B(4)), SEMITONE);
+    }
+
+    #[test]
+    fn frequencies_are_interpolated_between_semitones() {
+        assert_eq!(frequency(cents(Note::A(4))), (4, 577));
+        assert_eq!(frequency(cents(Note::A(4)) + 50), (4, 594));
+        assert_eq!(frequency(cents(Note::B(4)) + 50), (4, 665));
+        assert_eq!(frequency(cents(Note::C(5))), (5, 342));
+        assert_eq!(frequency(-100), (0, 342));
+        assert_eq!(frequency(cents(Note::B(9))), (7, 684));
+    }
+}
@@ -0,0 +1,628 @@
+use crate::backend::{SynthBackend, CHANNELS};
+use crate::dynamics::MAX_VOLUME;
+use crate::observer::Observer;
+use crate::sequencer::{Sequence, SequenceError};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// What a [Player] is doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerState {
+    Stopped,
+    Playing,
+    Paused,
+}
+
+/// What a [Player] does when it reaches the end of a song
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndBehaviour {
+    /// Key off all channels and stop
+    Stop,
+    /// Play the song again from the start
+    Loop,
+    /// Play the song again from the last [Action::Marker](crate::sequencer::Action::Marker) that was passed.
+    /// If there was none, the song is played from the start.
+    LoopFromMarker,
+    /// Play the next song of the playlist.
+    /// After the last song the player stops like with [EndBehaviour::Stop] and playing again starts at the first song.
+    NextSong,
+}
+
+/// Plays a sequence with transport controls like a tape deck.
+///
+/// The player keeps its own position in the sequence, so it only has to be told how many ticks went by.
+/// A copy of the sequence as it was given is kept so it can be played again from the start or from any other point.
+pub struct Player<B: SynthBackend, const N: usize, U = ()> {
+    playlist: Vec<Sequence<B, N, U>>,
+    song: usize,
+    sequence: Sequence<B, N, U>,
+    state: PlayerState,
+    position: u32,
+    end_behaviour: EndBehaviour,
+    volume: u8,
+}
+
+impl<B: SynthBackend, const N: usize, U: Clone> Player<B, N, U> {
+    /// Creates a stopped player at the start of the sequence
+    pub fn new(sequence: Sequence<B, N, U>) -> Self {
+        Self::with_playlist(vec![sequence])
+    }
+
+    /// Creates a stopped player at the start of the first song of the playlist
+    pub fn with_playlist(playlist: Vec<Sequence<B, N, U>>) -> Self {
+        assert!(!playlist.is_empty(), "A playlist needs at least one song");
+
+        Self {
+            sequence: playlist[0].clone(),
+            playlist,
+            song: 0,
+            state: PlayerState::Stopped,
+            position: 0,
+            end_behaviour: EndBehaviour::Stop,
+            volume: MAX_VOLUME,
+        }
+    }
+
+    pub fn state(&self) -> PlayerState {
+        self.state
+    }
+
+    /// The index of the song in the playlist that is playing
+    pub fn song(&self) -> usize {
+        self.song
+    }
+
+    /// Sets what happens at the end of a song.
+    /// The default is [EndBehaviour::Stop].
+    pub fn set_end_behaviour(&mut self, end_behaviour: EndBehaviour) {
+        self.end_behaviour = end_behaviour;
+    }
+
+    /// The timestamp in the sequence that was run last
+    pub fn position(&self) -> u32 {
+        self.position
+    }
+
+    pub fn sequence(&self) -> &Sequence<B, N, U> {
+        &self.sequence
+    }
+
+    /// The master volume, from 0 to [MAX_VOLUME]
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    /// Sets the master volume of the player. It's kept when the player goes to the start or to another song.
+    /// See [Sequence::set_volume].
+    pub fn set_volume(&mut self, backend: &mut B, volume: u8) -> Result<(), SequenceError<B::Error>> {
+        self.volume = volume.min(MAX_VOLUME);
+        self.sequence.set_volume(backend, volume)
+    }
+
+    /// See [Sequence::take_tempo_change]
+    pub fn take_tempo_change(&mut self) -> Option<u32> {
+        self.sequence.take_tempo_change()
+    }
+
+    /// Starts playing from the current position, or from the start if the sequence has played to the end.
+    ///
+    /// The notes that should be playing at this position are started,
+    /// but the events at the position itself only run on the next [Self::advance].
+    pub fn play(&mut self, backend: &mut B) -> Result<(), SequenceError<B::Error>> {
+        if self.state != PlayerState::Playing {
+            if self.sequence.next_timestamp().is_none() {
+                self.rewind();
+            }
+
+            self.start_sounding_notes(backend)?;
+            self.state = PlayerState::Playing;
+        }
+
+        Ok(())
+    }
+
+    /// Pauses the playback and releases all notes that are playing
+    pub fn pause(&mut self, backend: &mut B) -> Result<(), SequenceError<B::Error>> {
+        if self.state == PlayerState::Playing {
+            self.stop_sounding_notes(backend)?;
+            self.state = PlayerState::Paused;
+        }
+
+        Ok(())
+    }
+
+    /// Continues after a pause and starts the notes again that were released by it
+    pub fn resume(&mut self, backend: &mut B) -> Result<(), SequenceError<B::Error>> {
+        if self.state == PlayerState::Paused {
+            self.play(backend)?;
+        }
+
+        Ok(())
+    }
+
+    /// Stops the playback, releases all notes and goes back to the start
+    pub fn stop(&mut self, backend: &mut B) -> Result<(), SequenceError<B::Error>> {
+        if self.state == PlayerState::Playing {
+            self.stop_sounding_notes(backend)?;
+        }
+
+        self.rewind();
+        self.state = PlayerState::Stopped;
+
+        Ok(())
+    }
+
+    /// Goes to the timestamp in the sequence.
+    ///
+    /// The sequence is played from the start up to the timestamp without making a sound,
+    /// so the instruments, the tempo and the notes are the same as when the sequence would have been played normally.
+    /// The player stays in the state it was in.
+    pub fn seek(&mut self, backend: &mut B, timestamp: u32) -> Result<(), SequenceError<B::Error>> {
+        if self.state == PlayerState::Playing {
+            self.stop_sounding_notes(backend)?;
+        }
+
+        self.rewind();
+        self.sequence.fast_forward(backend, timestamp)?;
+        self.position = timestamp;
+
+        if self.state == PlayerState::Playing {
+            self.start_sounding_notes(backend)?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves the position forward by the amount of ticks and runs the sequence there.
+    /// Doesn't do anything when the player isn't playing.
+    ///
+    /// Returns false when the player is not playing (anymore).
+    /// What happens at the end of a song depends on the [EndBehaviour].
+    /// When the player stops there, the sequence can still be inspected, for example to see how many events were late.
+    pub fn advance(
+        &mut self,
+        backend: &mut B,
+        ticks: u32,
+    ) -> Result<bool, SequenceError<B::Error>> {
+        self.advance_observed(backend, ticks, &mut ())
+    }
+
+    /// Like [Self::advance], but tells the observer what the sequence is doing.
+    /// See [Sequence::run_observed].
+    pub fn advance_observed<O: Observer<U> + ?Sized>(
+        &mut self,
+        backend: &mut B,
+        ticks: u32,
+        observer: &mut O,
+    ) -> Result<bool, SequenceError<B::Error>> {
+        if self.state != PlayerState::Playing {
+            return Ok(false);
+        }
+
+        self.position = self.position.wrapping_add(ticks);
+        if !self.sequence.run_observed(backend, self.position, observer)? {
+            self.end(backend, observer)?;
+        }
+
+        Ok(self.state == PlayerState::Playing)
+    }
+
+    /// Handles the end of the song according to the end behaviour
+    fn end<O: Observer<U> + ?Sized>(
+        &mut self,
+        backend: &mut B,
+        observer: &mut O,
+    ) -> Result<(), SequenceError<B::Error>> {
+        let restart_at = match self.end_behaviour {
+            EndBehaviour::Stop => None,
+            EndBehaviour::Loop => Some(0),
+            EndBehaviour::LoopFromMarker => Some(self.sequence.last_marker().unwrap_or(0)),
+            EndBehaviour::NextSong if self.song + 1 < self.playlist.len() => {
+                self.song += 1;
+                Some(0)
+            }
+            EndBehaviour::NextSong => {
+                self.song = 0;
+                None
+            }
+        };
+
+        match restart_at {
+            Some(timestamp) => {
+                self.rewind();
+                self.sequence.fast_forward(backend, timestamp)?;
+                self.position = timestamp;
+                self.start_sounding_notes(backend)?;
+
+                // A song without any events would otherwise loop forever
+                if !self.sequence.run_observed(backend, self.position, observer)? {
+                    self.release_all(backend)?;
+                    self.state = PlayerState::Stopped;
+                }
+            }
+            None => {
+                self.release_all(backend)?;
+                self.state = PlayerState::Stopped;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The amount of ticks from the position to the next event.
+    /// This is `None` when the player is not playing.
+    pub fn ticks_to_next_event(&self) -> Option<u32> {
+        if self.state != PlayerState::Playing {
+            return None;
+        }
+
+        self.sequence
+            .next_timestamp()
+            .map(|next| next.saturating_sub(self.position))
+    }
+
+    fn rewind(&mut self) {
+        self.sequence = self.playlist[self.song].clone();
+        self.sequence.init_volume(self.volume);
+        self.position = 0;
+    }
+
+    fn start_sounding_notes(&mut self, backend: &mut B) -> Result<(), SequenceError<B::Error>> {
+        self.sequence.start_sounding_notes(backend)
+    }
+
+    /// Keys off every channel, also the ones the sequence doesn't know about
+    fn release_all(&mut self, backend: &mut B) -> Result<(), SequenceError<B::Error>> {
+        for channel in 0..CHANNELS {
+            backend.stop_note(channel).map_err(SequenceError::Backend)?;
+        }
+
+        Ok(())
+    }
+
+    fn stop_sounding_notes(&mut self, backend: &mut B) -> Result<(), SequenceError<B::Error>> {
+        for (channel, _) in self.sequence.sounding_notes() {
+            backend.stop_note(channel).map_err(SequenceError::Backend)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamics::{Levels, MAX_VELOCITY, MP};
+    use crate::mission_impossible::bass_instrument;
+    use crate::mock::{KeyEvent, MockInterface};
+    use crate::sequencer::{Action, ActionPoint, Instrument};
+    use alloc::vec;
+    use opl_driver::hl::{Melody, Note, Opl2};
+
+    type Opl = Opl2<MockInterface, Melody>;
+    type TestPlayer = Player<Opl, 16>;
+
+    fn play_note(channel: usize, duration: u32) -> Action<Opl> {
+        Action::PlayNote {
+            channel,
+            value: Note::A(4),
+            velocity: MAX_VELOCITY,
+            duration,
+        }
+    }
+
+    /// Two long notes after each other on channel 0 and a short one on channel 1 halfway the first
+    fn player() -> TestPlayer {
+        TestPlayer::new(Sequence::new(&[
+            ActionPoint::new(0, play_note(0, 10)),
+            ActionPoint::new(5, play_note(1, 2)),
+            ActionPoint::new(5, play_note(0, 10)),
+        ]))
+    }
+
+    /// Advances the player one tick at a time for the amount of ticks
+    fn advance(player: &mut TestPlayer, opl: &mut Opl, mock: &MockInterface, ticks: u32) {
+        for _ in 0..ticks {
+            mock.set_tick(player.position() + 1);
+            player.advance(opl, 1).unwrap();
+        }
+    }
+
+    #[test]
+    fn a_stopped_player_does_nothing() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut player = player();
+
+        assert_eq!(player.state(), PlayerState::Stopped);
+        assert!(!player.advance(&mut opl, 1).unwrap());
+        assert_eq!(player.ticks_to_next_event(), None);
+        assert!(mock.writes().is_empty());
+    }
+
+    #[test]
+    fn pause_releases_the_notes_and_resume_starts_them_again() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut player = player();
+
+        player.play(&mut opl).unwrap();
+        player.advance(&mut opl, 0).unwrap();
+        advance(&mut player, &mut opl, &mock, 6);
+
+        player.pause(&mut opl).unwrap();
+        assert_eq!(player.state(), PlayerState::Paused);
+        assert!(!player.advance(&mut opl, 1).unwrap());
+        assert_eq!(player.position(), 6);
+
+        mock.set_tick(100);
+        player.resume(&mut opl).unwrap();
+        assert_eq!(player.state(), PlayerState::Playing);
+
+        assert_eq!(
+            mock.key_events(),
+            vec![
+                KeyEvent::on(0, 0),
+                KeyEvent::on(5, 1),
+                KeyEvent::off(6, 0),
+                KeyEvent::off(6, 1),
+                KeyEvent::on(100, 0),
+                KeyEvent::on(100, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn the_volume_is_kept_when_going_back_to_the_start() {
+        let (mut opl, _) = MockInterface::opl();
+        let mut player = player();
+
+        player.set_volume(&mut opl, 200).unwrap();
+        assert_eq!(player.volume(), MAX_VOLUME);
+
+        player.set_volume(&mut opl, 50).unwrap();
+        player.play(&mut opl).unwrap();
+        player.advance(&mut opl, 3).unwrap();
+        player.stop(&mut opl).unwrap();
+
+        assert_eq!(player.sequence().volume(), 50);
+    }
+
+    #[test]
+    fn seek_starts_the_notes_that_should_be_playing() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut player = player();
+
+        player.seek(&mut opl, 6).unwrap();
+        assert!(mock.key_events().is_empty());
+
+        mock.set_tick(6);
+        player.play(&mut opl).unwrap();
+        player.advance(&mut opl, 0).unwrap();
+        advance(&mut player, &mut opl, &mock, 4);
+
+        assert_eq!(
+            mock.key_events(),
+            vec![KeyEvent::on(6, 0), KeyEvent::on(6, 1), KeyEvent::off(7, 1), KeyEvent::off(10, 0), KeyEvent::on(10, 0)]
+        );
+    }
+
+    #[test]
+    fn seek_keeps_the_velocity_of_the_notes_that_are_held() {
+        let sequence = || {
+            Sequence::new(&[
+                ActionPoint::new(
+                    0,
+                    Action::SetInstrument {
+                        channel: 0,
+                        instrument: 0,
+                    },
+                ),
+                ActionPoint::new(
+                    0,
+                    Action::PlayNote {
+                        channel: 0,
+                        value: Note::A(4),
+                        velocity: MP,
+                        duration: 10,
+                    },
+                ),
+            ])
+            .with_instruments(&[Instrument::new(bass_instrument()).with_levels(Levels::fm(16, 0))])
+        };
+        // Channel 0 has its carrier at 0x03
+        let carrier_level = |mock: &MockInterface| {
+            mock.writes()
+                .iter()
+                .rev()
+                .find(|write| write.address == 0x43)
+                .map(|write| write.value)
+        };
+
+        let (mut opl, mock) = MockInterface::opl();
+        let mut player = TestPlayer::new(sequence());
+        player.play(&mut opl).unwrap();
+        player.advance(&mut opl, 0).unwrap();
+        let played = carrier_level(&mock);
+        assert!(played.is_some());
+
+        let (mut opl, mock) = MockInterface::opl();
+        let mut player = TestPlayer::new(sequence());
+        player.seek(&mut opl, 5).unwrap();
+        player.play(&mut opl).unwrap();
+        assert_eq!(carrier_level(&mock), played);
+        assert_eq!(mock.key_events(), vec![KeyEvent::on(0, 0)]);
+    }
+
+    #[test]
+    fn seek_sets_up_the_instruments() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut player = TestPlayer::new(Sequence::new(&[
+            ActionPoint::new(
+                0,
+                Action::Custom {
+                    function: |opl: &mut Opl| opl.set_instrument(0, bass_instrument()),
+                },
+            ),
+            ActionPoint::new(10, play_note(0, 10)),
+        ]));
+
+        player.seek(&mut opl, 5).unwrap();
+
+        assert!(!mock.writes().is_empty());
+        assert!(mock.key_events().is_empty());
+        assert_eq!(player.ticks_to_next_event(), None);
+    }
+
+    #[test]
+    fn stop_goes_back_to_the_start() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut player = player();
+
+        player.play(&mut opl).unwrap();
+        player.advance(&mut opl, 0).unwrap();
+        advance(&mut player, &mut opl, &mock, 3);
+        player.stop(&mut opl).unwrap();
+
+        assert_eq!(player.state(), PlayerState::Stopped);
+        assert_eq!(player.position(), 0);
+        assert_eq!(mock.key_events(), vec![KeyEvent::on(0, 0), KeyEvent::off(3, 0)]);
+
+        player.play(&mut opl).unwrap();
+        assert_eq!(player.ticks_to_next_event(), Some(0));
+    }
+
+    #[test]
+    fn the_player_stops_at_the_end() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut player = player();
+
+        player.play(&mut opl).unwrap();
+        player.advance(&mut opl, 0).unwrap();
+
+        let mut ticks = 0;
+        while let Some(next) = player.ticks_to_next_event() {
+            ticks += next;
+            mock.set_tick(ticks);
+            player.advance(&mut opl, next).unwrap();
+        }
+
+        assert_eq!(player.state(), PlayerState::Stopped);
+        assert_eq!(player.position(), 20);
+        assert_eq!(ticks, 20);
+        assert_eq!(mock.key_events().len(), 6);
+
+        // Playing again starts over
+        player.play(&mut opl).unwrap();
+        assert_eq!(player.position(), 0);
+        assert_eq!(player.ticks_to_next_event(), Some(0));
+    }
+
+    /// Plays until the player stops or the tick limit is reached
+    fn play_to_end(player: &mut TestPlayer, opl: &mut Opl, mock: &MockInterface, max_ticks: u32) {
+        player.play(opl).unwrap();
+        mock.set_tick(player.position());
+        player.advance(opl, 0).unwrap();
+
+        let mut ticks = 0;
+        while let Some(next) = player.ticks_to_next_event() {
+            ticks += next;
+            if ticks > max_ticks {
+                break;
+            }
+
+            mock.set_tick(ticks);
+            player.advance(opl, next).unwrap();
+        }
+    }
+
+    #[test]
+    fn stopping_at_the_end_keys_off_every_channel() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut player = TestPlayer::new(Sequence::new(&[
+            ActionPoint::new(0, play_note(0, 4)),
+            ActionPoint::new(
+                0,
+                Action::NoteOn {
+                    channel: 1,
+                    value: Note::C(4),
+                    velocity: MAX_VELOCITY,
+                },
+            ),
+        ]));
+
+        play_to_end(&mut player, &mut opl, &mock, 100);
+
+        assert_eq!(player.state(), PlayerState::Stopped);
+        assert_eq!(
+            mock.key_events(),
+            vec![KeyEvent::on(0, 1), KeyEvent::on(0, 0), KeyEvent::off(4, 0), KeyEvent::off(4, 1)]
+        );
+    }
+
+    #[test]
+    fn loop_plays_the_song_again() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut player = player();
+        player.set_end_behaviour(EndBehaviour::Loop);
+
+        play_to_end(&mut player, &mut opl, &mock, 45);
+
+        assert_eq!(player.state(), PlayerState::Playing);
+        assert_eq!(
+            mock.key_events()
+                .iter()
+                .filter(|e| e.on && e.channel == 1)
+                .collect::<alloc::vec::Vec<_>>(),
+            vec![&KeyEvent::on(5, 1), &KeyEvent::on(25, 1), &KeyEvent::on(45, 1)]
+        );
+    }
+
+    #[test]
+    fn loop_from_marker_goes_back_to_the_last_marker() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut player = TestPlayer::new(Sequence::new(&[
+            ActionPoint::new(0, play_note(0, 2)),
+            ActionPoint::new(5, Action::Marker { id: 0 }),
+            ActionPoint::new(0, play_note(1, 2)),
+        ]));
+        player.set_end_behaviour(EndBehaviour::LoopFromMarker);
+
+        play_to_end(&mut player, &mut opl, &mock, 12);
+
+        // The loop is 2 ticks long: from the marker at 5 to the end at 7
+        assert_eq!(
+            mock.key_events(),
+            vec![
+                KeyEvent::on(0, 0),
+                KeyEvent::off(2, 0),
+                KeyEvent::on(5, 1),
+                KeyEvent::off(7, 1),
+                KeyEvent::on(7, 1),
+                KeyEvent::off(9, 1),
+                KeyEvent::on(9, 1),
+                KeyEvent::off(11, 1),
+                KeyEvent::on(11, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn next_song_plays_the_playlist() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut player = TestPlayer::with_playlist(vec![
+            Sequence::new(&[ActionPoint::new(0, play_note(0, 2))]),
+            Sequence::new(&[ActionPoint::new(1, play_note(1, 2))]),
+        ]);
+        player.set_end_behaviour(EndBehaviour::NextSong);
+
+        play_to_end(&mut player, &mut opl, &mock, 100);
+
+        assert_eq!(player.state(), PlayerState::Stopped);
+        assert_eq!(
+            mock.key_events(),
+            vec![KeyEvent::on(0, 0), KeyEvent::off(2, 0), KeyEvent::on(3, 1), KeyEvent::off(5, 1)]
+        );
+
+        player.play(&mut opl).unwrap();
+        assert_eq!(player.song(), 0);
+    }
+}
@@ -0,0 +1,172 @@
+use core::cmp::Ordering;
+use heapless::binary_heap::{BinaryHeap, Min};
+
+/// A priority queue of timestamped values that doesn't use the heap.
+///
+/// Values with the same timestamp come out in the order they were pushed.
+/// Pushing and popping are both `O(log N)`.
+pub struct EventQueue<T, const N: usize> {
+    heap: BinaryHeap<Entry<T>, Min, N>,
+    next_order: u32,
+}
+
+impl<T, const N: usize> EventQueue<T, N> {
+    pub fn new() -> Self {
+        Self {
+            heap: BinaryHeap::new(),
+            next_order: 0,
+        }
+    }
+
+    /// Pushes the value into the queue.
+    /// If the queue is full, the value is given back.
+    pub fn push(&mut self, timestamp: u32, value: T) -> Result<(), T> {
+        let order = self.next_order;
+
+        self.heap
+            .push(Entry {
+                timestamp,
+                order,
+                value,
+            })
+            .map_err(|entry| entry.value)?;
+
+        self.next_order = self.next_order.wrapping_add(1);
+        Ok(())
+    }
+
+    /// Gets the timestamp of the earliest value
+    pub fn peek_timestamp(&self) -> Option<u32> {
+        self.heap.peek().map(|entry| entry.timestamp)
+    }
+
+    /// Gets the earliest value and its timestamp without taking it out
+    pub fn peek(&self) -> Option<(u32, &T)> {
+        self.heap.peek().map(|entry| (entry.timestamp, &entry.value))
+    }
+
+    /// Takes out the earliest value
+    pub fn pop(&mut self) -> Option<(u32, T)> {
+        self.heap.pop().map(|entry| (entry.timestamp, entry.value))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// Goes over all values and their timestamps, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &T)> {
+        self.heap.iter().map(|entry| (entry.timestamp, &entry.value))
+    }
+}
+
+impl<T, const N: usize> Default for EventQueue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for EventQueue<T, N> {
+    fn clone(&self) -> Self {
+        Self {
+            heap: self.heap.clone(),
+            next_order: self.next_order,
+        }
+    }
+}
+
+struct Entry<T> {
+    timestamp: u32,
+    /// Used to keep values with the same timestamp in push order
+    order: u32,
+    value: T,
+}
+
+impl<T> Entry<T> {
+    fn key(&self) -> (u32, u32) {
+        (self.timestamp, self.order)
+    }
+}
+
+impl<T: Clone> Clone for Entry<T> {
+    fn clone(&self) -> Self {
+        Self {
+            timestamp: self.timestamp,
+            order: self.order,
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<T> PartialEq for Entry<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<T> Eq for Entry<T> {}
+
+impl<T> PartialOrd for Entry<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Entry<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn drain<const N: usize>(queue: &mut EventQueue<char, N>) -> Vec<(u32, char)> {
+        core::iter::from_fn(|| queue.pop()).collect()
+    }
+
+    #[test]
+    fn pops_in_timestamp_order() {
+        let mut queue = EventQueue::<char, 8>::new();
+
+        queue.push(30, 'a').unwrap();
+        queue.push(10, 'b').unwrap();
+        queue.push(20, 'c').unwrap();
+        queue.push(0, 'd').unwrap();
+
+        assert_eq!(queue.peek_timestamp(), Some(0));
+        assert_eq!(
+            drain(&mut queue),
+            vec![(0, 'd'), (10, 'b'), (20, 'c'), (30, 'a')]
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn equal_timestamps_keep_push_order() {
+        let mut queue = EventQueue::<char, 8>::new();
+
+        for (timestamp, value) in [(5, 'a'), (1, 'b'), (5, 'c'), (1, 'd'), (5, 'e')].iter() {
+            queue.push(*timestamp, *value).unwrap();
+        }
+
+        assert_eq!(
+            drain(&mut queue),
+            vec![(1, 'b'), (1, 'd'), (5, 'a'), (5, 'c'), (5, 'e')]
+        );
+    }
+
+    #[test]
+    fn full_queue_gives_the_value_back() {
+        let mut queue = EventQueue::<char, 2>::new();
+
+        queue.push(0, 'a').unwrap();
+        queue.push(0, 'b').unwrap();
+        assert_eq!(queue.push(0, 'c'), Err('c'));
+
+        assert_eq!(drain(&mut queue), vec![(0, 'a'), (0, 'b')]);
+    }
+}
@@ -0,0 +1,3149 @@
+use core::fmt::Display;
+
+use crate::automation::{Lane, Parameter};
+use crate::backend::{SynthBackend, CHANNELS};
+use crate::curve::Curve;
+use crate::dynamics::{Levels, MAX_VELOCITY, MAX_VOLUME};
+use crate::effects::Effect;
+use crate::observer::Observer;
+use crate::pitch::{self, SEMITONE};
+use crate::queue::EventQueue;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use opl_driver::hl::Note;
+use opl_driver::instrument::MelodyInstrument;
+
+/// The maximum amount of [Action::Jump]s a sequence can keep count of
+pub const MAX_JUMPS: usize = 16;
+
+/// The maximum amount of [Action::Marker]s a sequence keeps a snapshot of, so a jump back to them doesn't have to replay the sequence.
+/// Every snapshot has a copy of the queue, so this is what most of the memory of a sequence goes to.
+pub const MAX_MARKER_SNAPSHOTS: usize = 4;
+
+/// A playable sequence of actions.
+///
+/// The pending actions are kept in a queue that can hold `N` events.
+/// Building a sequence allocates, but running it doesn't, so it can be played from an interrupt.
+///
+/// `U` is the type of the [Action::User] events, which are passed on to the [Observer].
+///
+/// The events in the queue are timed in the time of the score.
+/// After a jump, the score time no longer matches the timestamps the sequence is run with,
+/// so the difference between the two is kept as an offset.
+/// The timestamps the sequence is run with may wrap around, because they're only compared after the offset is taken off.
+/// The timestamps of the score can't: the queue orders them as plain numbers,
+/// so a sequence plays at most `u32::MAX` ticks from its start before its events come out in the wrong order.
+/// Only an [Action::Loop] gets that far, at 64 ticks per second that's after more than two years.
+pub struct Sequence<B: SynthBackend, const N: usize, U = ()> {
+    queue: EventQueue<Event<B, U>, N>,
+    /// The queue as it was before the sequence started, used for jumps
+    start: EventQueue<Event<B, U>, N>,
+    /// The run timestamp minus the score timestamp
+    offset: u32,
+    /// How many times each jump was taken, by the score timestamp of the jump
+    jump_counts: heapless::Vec<(u32, u32), MAX_JUMPS>,
+    /// Set after the first jump, so a [Action::ToCoda] knows it's on the way back
+    jumped: bool,
+    late_policy: LatePolicy,
+    late_events: u32,
+    tempo: Option<u32>,
+    tempo_changed: bool,
+    /// The note that is playing on each channel
+    notes: [Option<Note>; CHANNELS],
+    /// When set, notes are only tracked and not sent to the backend
+    muted: bool,
+    /// The timestamp of the last [Action::Marker] that ran
+    last_marker: Option<u32>,
+    /// The instruments that [Action::SetInstrument] refers to
+    instruments: Arc<[Instrument]>,
+    /// The index of the instrument that was set on each channel
+    channel_instruments: [Option<usize>; CHANNELS],
+    /// The levels of the instrument that was set on each channel, if it has them
+    levels: [Option<Levels>; CHANNELS],
+    /// The velocity the levels of each channel were last set for
+    velocities: [Option<u8>; CHANNELS],
+    /// The master volume that is set by the application
+    volume: u8,
+    /// The volume of the fade the sequence is in, on top of the master volume
+    fade: u8,
+    /// Counts the fades, so a fade stops when a new one starts
+    fade_id: u32,
+    /// The pitch in cents that each channel is playing at, which is different from its note while it slides
+    pitches: [i32; CHANNELS],
+    /// Counts the notes and the slides of each channel, so a slide stops when a new one or a new note starts
+    slide_ids: [u32; CHANNELS],
+    /// The pitch offset in cents of each channel that is set by a [Parameter::Pitch] lane
+    pitch_offsets: [i32; CHANNELS],
+    /// Counts the lanes of every parameter of each channel, so a lane stops when a new one for the same parameter starts
+    lane_ids: [[u32; Parameter::COUNT]; CHANNELS],
+    /// Counts the effects of each channel, so an effect stops when a new one starts
+    effect_ids: [u32; CHANNELS],
+    /// The timestamp and the ticks of the last [Effect::NoteDelay] of each channel
+    note_delays: [Option<(u32, u32)>; CHANNELS],
+    /// The state right before the markers the sequence passed, for the jumps back to them
+    snapshots: heapless::Vec<Snapshot<B, N, U>, MAX_MARKER_SNAPSHOTS>,
+}
+
+impl<B: SynthBackend, const N: usize, U: Clone> Sequence<B, N, U> {
+    pub fn new(relative_points: &[ActionPoint<B, U>]) -> Self {
+        let mut sequence = Self {
+            queue: EventQueue::new(),
+            start: EventQueue::new(),
+            offset: 0,
+            jump_counts: heapless::Vec::new(),
+            jumped: false,
+            late_policy: LatePolicy::RunLate,
+            late_events: 0,
+            tempo: None,
+            tempo_changed: false,
+            notes: [None; CHANNELS],
+            muted: false,
+            last_marker: None,
+            instruments: Vec::new().into(),
+            channel_instruments: [None; CHANNELS],
+            levels: [None; CHANNELS],
+            velocities: [None; CHANNELS],
+            volume: MAX_VOLUME,
+            fade: MAX_VOLUME,
+            fade_id: 0,
+            pitches: [0; CHANNELS],
+            slide_ids: [0; CHANNELS],
+            pitch_offsets: [0; CHANNELS],
+            lane_ids: [[0; Parameter::COUNT]; CHANNELS],
+            effect_ids: [0; CHANNELS],
+            note_delays: [None; CHANNELS],
+            snapshots: heapless::Vec::new(),
+        };
+
+        if let Some(pattern_start) = Pattern::new(relative_points).start(0) {
+            sequence
+                .insert(pattern_start)
+                .unwrap_or_else(|_| panic!("A sequence needs a capacity of at least 1"));
+        }
+
+        sequence.start = sequence.queue.clone();
+        sequence
+    }
+
+    /// Sets the instrument table that the [Action::SetInstrument]s of the sequence refer to by index
+    pub fn with_instruments<I: Clone + Into<Instrument>>(mut self, instruments: &[I]) -> Self {
+        self.instruments = instruments
+            .iter()
+            .map(|instrument| instrument.clone().into())
+            .collect::<Vec<_>>()
+            .into();
+        self
+    }
+
+    pub fn instruments(&self) -> &[Instrument] {
+        &self.instruments
+    }
+
+    /// Adds the events of the other sequence to this one.
+    /// The instruments of the other sequence are not taken over, so both have to use the instrument table of this one.
+    pub fn merge(&mut self, mut other: Self) -> Result<(), SequenceError<B::Error>> {
+        while let Some(event) = other.queue.pop() {
+            self.insert(event)?;
+        }
+
+        while let Some((timestamp, event)) = other.start.pop() {
+            self.start
+                .push(timestamp, event)
+                .map_err(|_| SequenceError::QueueFull)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks the whole sequence for mistakes that would otherwise only show up when it's played:
+    /// channels that don't exist, instruments that aren't in the table, tempos of 0 bpm and jumps to markers that aren't there.
+    pub fn validate(&self) -> Result<(), SequenceError<B::Error>> {
+        let mut result = Ok(());
+
+        self.visit_actions(&mut |action| {
+            let invalid_channel = action
+                .channel()
+                .filter(|channel| *channel >= CHANNELS)
+                .map(|channel| SequenceError::InvalidChannel { channel });
+
+            let error = invalid_channel.or(match *action {
+                Action::SetInstrument { instrument, .. } if instrument >= self.instruments.len() => {
+                    Some(SequenceError::UnknownInstrument { index: instrument })
+                }
+                Action::SetTempo { bpm: 0 }
+                | Action::TempoRamp { from_bpm: 0, .. }
+                | Action::TempoRamp { to_bpm: 0, .. } => Some(SequenceError::InvalidTempo),
+                Action::Jump {
+                    target: JumpTarget::Marker(id),
+                    ..
+                }
+                | Action::ToCoda { coda: id }
+                    if !self.has_marker(id) =>
+                {
+                    Some(SequenceError::UnknownMarker { id })
+                }
+                _ => None,
+            });
+
+            if let (Some(error), Ok(())) = (error, &result) {
+                result = Err(error);
+            }
+        });
+
+        result
+    }
+
+    fn has_marker(&self, id: u32) -> bool {
+        let mut found = false;
+        self.visit_actions(&mut |action| {
+            found |= matches!(action, Action::Marker { id: marker_id } if *marker_id == id);
+        });
+        found
+    }
+
+    /// Calls the function for every action of the sequence as it was built, including the actions in patterns
+    pub(crate) fn visit_actions(&self, f: &mut impl FnMut(&Action<B, U>)) {
+        for (_, event) in self.start.iter() {
+            match event {
+                Event::Action(action) => visit_action(action, f),
+                Event::Pattern { pattern, index, .. } => pattern.points[*index..]
+                    .iter()
+                    .for_each(|point| visit_action(&point.value, f)),
+                Event::TempoRamp { .. }
+                | Event::Fade { .. }
+                | Event::Slide { .. }
+                | Event::Lane { .. }
+                | Event::Effect { .. } => {}
+            }
+        }
+    }
+
+    /// The master volume, from 0 to [MAX_VOLUME]
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    /// Sets the master volume, from 0 to [MAX_VOLUME].
+    ///
+    /// Like velocity, this only works for the channels that have an instrument with [Levels].
+    /// The levels of the channels that played a note are rewritten right away, so notes that are held get softer too.
+    pub fn set_volume(&mut self, backend: &mut B, volume: u8) -> Result<(), SequenceError<B::Error>> {
+        self.volume = volume.min(MAX_VOLUME);
+        self.update_levels(backend)
+    }
+
+    /// Sets the master volume without writing the levels.
+    /// Only use this on a sequence that hasn't run yet.
+    pub(crate) fn init_volume(&mut self, volume: u8) {
+        self.volume = volume.min(MAX_VOLUME);
+    }
+
+    /// Sets what happens with events that should have run before the timestamp that is given to [Self::run].
+    /// The default is [LatePolicy::RunLate].
+    pub fn set_late_policy(&mut self, late_policy: LatePolicy) {
+        self.late_policy = late_policy;
+    }
+
+    /// The amount of events that were late.
+    ///
+    /// Events that are created by a late event (like the note on of a late [Action::PlayNote]) are late as well and are counted too.
+    pub fn late_events(&self) -> u32 {
+        self.late_events
+    }
+
+    /// The tempo in bpm that was set by the last [Action::SetTempo]
+    pub fn tempo(&self) -> Option<u32> {
+        self.tempo
+    }
+
+    /// Returns the new tempo if an [Action::SetTempo] ran since the last time this was called.
+    ///
+    /// The sequence doesn't know how its timestamps are generated,
+    /// so whoever runs the sequence must check this after every run and change the tick rate when needed.
+    pub fn take_tempo_change(&mut self) -> Option<u32> {
+        if core::mem::replace(&mut self.tempo_changed, false) {
+            self.tempo
+        } else {
+            None
+        }
+    }
+
+    /// The timestamp of the next event, or `None` when the sequence is done.
+    ///
+    /// Nothing happens between now and then, so the sequence doesn't need to be run before that.
+    pub fn next_timestamp(&self) -> Option<u32> {
+        self.queue
+            .peek_timestamp()
+            .map(|timestamp| timestamp.wrapping_add(self.offset))
+    }
+
+    pub fn run(&mut self, backend: &mut B, timestamp: u32) -> Result<bool, SequenceError<B::Error>> {
+        self.run_observed(backend, timestamp, &mut ())
+    }
+
+    /// Like [Self::run], but tells the observer about the markers, the notes and the end of the sequence
+    pub fn run_observed<O: Observer<U> + ?Sized>(
+        &mut self,
+        backend: &mut B,
+        timestamp: u32,
+        observer: &mut O,
+    ) -> Result<bool, SequenceError<B::Error>> {
+        let mut last_run = None;
+
+        while let Some(next_timestamp) = self.queue.peek_timestamp() {
+            // Checked every time, because a jump changes the offset
+            let score_timestamp = timestamp.wrapping_sub(self.offset);
+            if next_timestamp > score_timestamp {
+                break;
+            }
+
+            self.snapshot_marker();
+
+            let late = next_timestamp < score_timestamp;
+            if late {
+                self.late_events = self.late_events.saturating_add(1);
+
+                if self.late_policy == LatePolicy::Error {
+                    // Taken out like with LatePolicy::Drop, so the next run goes on with the events after it
+                    let (next_timestamp, event) = self.queue.pop().unwrap();
+                    self.run_event(backend, observer, next_timestamp, event, true)?;
+
+                    return Err(SequenceError::LateEvent {
+                        scheduled: next_timestamp.wrapping_add(self.offset),
+                        timestamp,
+                    });
+                }
+            }
+
+            // Execute the action.
+            // Late events keep their own timestamp so that the events they create stay in time with the rest.
+            let (next_timestamp, event) = self.queue.pop().unwrap();
+            last_run = Some(next_timestamp.wrapping_add(self.offset));
+            self.run_event(backend, observer, next_timestamp, event, late)?;
+        }
+
+        match last_run {
+            Some(last_timestamp) if self.queue.is_empty() => observer.on_end(last_timestamp),
+            _ => {}
+        }
+
+        Ok(!self.queue.is_empty())
+    }
+
+    /// Runs all events before the score timestamp without making a sound.
+    ///
+    /// Everything except the notes and the jumps runs like normal, so the instruments are still set up and the tempo is still followed.
+    /// The notes and their levels are only tracked, so afterwards [Self::start_sounding_notes] starts the notes that should be playing at the timestamp.
+    pub(crate) fn fast_forward(&mut self, backend: &mut B, timestamp: u32) -> Result<(), SequenceError<B::Error>> {
+        self.fast_forward_until(backend, |next_timestamp, _| next_timestamp >= timestamp)
+    }
+
+    /// Like [Self::fast_forward], but runs until the next event is one for which `stop` returns true
+    fn fast_forward_until(
+        &mut self,
+        backend: &mut B,
+        stop: impl Fn(u32, &Event<B, U>) -> bool,
+    ) -> Result<(), SequenceError<B::Error>> {
+        self.muted = true;
+
+        let mut result = Ok(());
+        while let Some((next_timestamp, next_event)) = self.queue.peek() {
+            if stop(next_timestamp, next_event) {
+                break;
+            }
+
+            self.snapshot_marker();
+            let (next_timestamp, event) = self.queue.pop().unwrap();
+            result = self.run_event(backend, &mut (), next_timestamp, event, false);
+            if result.is_err() {
+                break;
+            }
+        }
+
+        self.muted = false;
+        result
+    }
+
+    /// Moves the whole sequence so that it starts at the timestamp instead of at 0.
+    /// Only use this on a sequence that hasn't run yet.
+    pub(crate) fn start_at(&mut self, timestamp: u32) {
+        self.offset = timestamp;
+        self.reset_channels();
+    }
+
+    /// Puts the state of the channels and the fade back to how it is before the sequence starts.
+    /// The instrument table, the master volume and the jump counts are kept.
+    fn reset_channels(&mut self) {
+        self.notes = [None; CHANNELS];
+        self.channel_instruments = [None; CHANNELS];
+        self.levels = [None; CHANNELS];
+        self.velocities = [None; CHANNELS];
+        self.fade = MAX_VOLUME;
+        self.pitches = [0; CHANNELS];
+        self.pitch_offsets = [0; CHANNELS];
+        self.note_delays = [None; CHANNELS];
+
+        // Stops the fade, slides, lanes and effects that might still come back through events that are queued
+        self.fade_id = self.fade_id.wrapping_add(1);
+        for channel in 0..CHANNELS {
+            self.slide_ids[channel] = self.slide_ids[channel].wrapping_add(1);
+            self.effect_ids[channel] = self.effect_ids[channel].wrapping_add(1);
+            for lane_id in self.lane_ids[channel].iter_mut() {
+                *lane_id = lane_id.wrapping_add(1);
+            }
+        }
+    }
+
+    /// The timestamp of the last [Action::Marker] that ran.
+    /// This is in the time of the score, so jumps that were taken are not counted.
+    pub fn last_marker(&self) -> Option<u32> {
+        self.last_marker
+    }
+
+    /// Continues the sequence at the target.
+    ///
+    /// A marker that the sequence passed before has a snapshot, which puts everything back like it was right before the marker.
+    /// Otherwise the sequence is started over from the beginning and fast forwarded to the target,
+    /// so everything is set up like it would have been if the target was reached normally.
+    /// Either way nothing of the state of the channels from before the jump is carried over.
+    ///
+    /// The fast forward runs every event from the start to the target, so that only happens for the first [MAX_MARKER_SNAPSHOTS] markers
+    /// before they are passed (like a coda), or for the markers after them.
+    fn jump(&mut self, backend: &mut B, score_timestamp: u32, target: JumpTarget) -> Result<(), SequenceError<B::Error>> {
+        let timestamp = score_timestamp.wrapping_add(self.offset);
+
+        for (channel, note) in self.notes.iter_mut().enumerate() {
+            if note.take().is_some() {
+                backend.stop_note(channel).map_err(SequenceError::Backend)?;
+            }
+        }
+
+        self.jumped = true;
+
+        let target_timestamp = match target {
+            JumpTarget::Start => {
+                self.reset_channels();
+                self.queue = self.start.clone();
+                0
+            }
+            JumpTarget::Marker(id) => {
+                match self.snapshots.iter().position(|snapshot| snapshot.marker == id) {
+                    Some(index) => self.restore(backend, index)?,
+                    None => {
+                        self.reset_channels();
+                        self.queue = self.start.clone();
+
+                        // Stop right before the marker, so it runs like normal and the observer sees it
+                        self.fast_forward_until(backend, |_, event| event.marker_id() == Some(id))?;
+                    }
+                }
+
+                self.queue
+                    .peek_timestamp()
+                    .ok_or(SequenceError::UnknownMarker { id })?
+            }
+        };
+        self.offset = timestamp.wrapping_sub(target_timestamp);
+
+        self.start_sounding_notes(backend)
+    }
+
+    /// Keeps a snapshot of the sequence if the next event is a marker that doesn't have one yet.
+    /// When there's no room left, the jumps to the marker fast forward instead.
+    fn snapshot_marker(&mut self) {
+        let marker = match self.queue.peek() {
+            Some((_, event)) => event.marker_id(),
+            None => None,
+        };
+
+        match marker {
+            Some(marker) if !self.snapshots.is_full() && self.snapshots.iter().all(|snapshot| snapshot.marker != marker) => {
+                let _ = self.snapshots.push(Snapshot {
+                    marker,
+                    queue: self.queue.clone(),
+                    tempo: self.tempo,
+                    last_marker: self.last_marker,
+                    notes: self.notes,
+                    channel_instruments: self.channel_instruments,
+                    levels: self.levels,
+                    velocities: self.velocities,
+                    fade: self.fade,
+                    fade_id: self.fade_id,
+                    pitches: self.pitches,
+                    slide_ids: self.slide_ids,
+                    pitch_offsets: self.pitch_offsets,
+                    lane_ids: self.lane_ids,
+                    effect_ids: self.effect_ids,
+                    note_delays: self.note_delays,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    /// Puts the sequence back in the state of the snapshot with the index.
+    /// The instruments that changed since are set up again, [Action::Custom]s from before the marker don't run again.
+    fn restore(&mut self, backend: &mut B, index: usize) -> Result<(), SequenceError<B::Error>> {
+        let snapshot = &self.snapshots[index];
+
+        for (channel, instrument) in snapshot.channel_instruments.iter().enumerate() {
+            match instrument {
+                Some(instrument) if self.channel_instruments[channel] != Some(*instrument) => backend
+                    .set_instrument(channel, self.instruments[*instrument].melody.clone())
+                    .map_err(SequenceError::Backend)?,
+                _ => {}
+            }
+        }
+
+        self.queue = snapshot.queue.clone();
+        self.last_marker = snapshot.last_marker;
+        self.notes = snapshot.notes;
+        self.channel_instruments = snapshot.channel_instruments;
+        self.levels = snapshot.levels;
+        self.velocities = snapshot.velocities;
+        self.fade = snapshot.fade;
+        self.fade_id = snapshot.fade_id;
+        self.pitches = snapshot.pitches;
+        self.slide_ids = snapshot.slide_ids;
+        self.pitch_offsets = snapshot.pitch_offsets;
+        self.lane_ids = snapshot.lane_ids;
+        self.effect_ids = snapshot.effect_ids;
+        self.note_delays = snapshot.note_delays;
+
+        if let Some(bpm) = snapshot.tempo {
+            self.set_tempo(bpm);
+        }
+
+        Ok(())
+    }
+
+    /// Counts the jump at the score timestamp and returns true if it has been taken less than `times` times before
+    fn count_jump(&mut self, score_timestamp: u32, times: u32) -> Result<bool, SequenceError<B::Error>> {
+        let index = match self
+            .jump_counts
+            .iter()
+            .position(|(timestamp, _)| *timestamp == score_timestamp)
+        {
+            Some(index) => index,
+            None => {
+                self.jump_counts
+                    .push((score_timestamp, 0))
+                    .map_err(|_| SequenceError::TooManyJumps)?;
+                self.jump_counts.len() - 1
+            }
+        };
+
+        let (_, count) = &mut self.jump_counts[index];
+        if *count < times {
+            *count += 1;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Starts the notes that should be playing, after a fast forward or after they were released for a pause.
+    /// The levels of every channel are written first, because a fast forward only keeps track of them.
+    pub(crate) fn start_sounding_notes(&mut self, backend: &mut B) -> Result<(), SequenceError<B::Error>> {
+        self.update_levels(backend)?;
+
+        for (channel, note) in self.sounding_notes() {
+            backend
+                .start_note(channel, note)
+                .map_err(SequenceError::Backend)?;
+        }
+
+        Ok(())
+    }
+
+    /// The channels that have a note playing and the note that's playing on them
+    pub(crate) fn sounding_notes(&self) -> impl Iterator<Item = (usize, Note)> + '_ {
+        self.notes
+            .iter()
+            .enumerate()
+            .filter_map(|(channel, note)| note.map(|note| (channel, note)))
+    }
+
+    fn run_event<O: Observer<U> + ?Sized>(
+        &mut self,
+        backend: &mut B,
+        observer: &mut O,
+        timestamp: u32,
+        event: Event<B, U>,
+        late: bool,
+    ) -> Result<(), SequenceError<B::Error>> {
+        match event {
+            Event::Action(action) => self.run_action(backend, observer, timestamp, action, late),
+            Event::Pattern {
+                pattern,
+                index,
+                start,
+            } => {
+                let action = pattern.points[index].value.clone();
+
+                // Only the next point of the pattern is queued, so a pattern takes up a single place in the queue
+                if let Some(next) = pattern.next(index, start) {
+                    self.insert(next)?;
+                }
+
+                self.run_action(backend, observer, timestamp, action, late)
+            }
+            Event::TempoRamp {
+                from_bpm,
+                to_bpm,
+                duration,
+                curve,
+                start,
+            } => {
+                let elapsed = timestamp.wrapping_sub(start);
+                let bpm = curve.value(from_bpm as f32, to_bpm as f32, elapsed, duration);
+                self.set_tempo(libm::roundf(bpm) as u32);
+
+                if elapsed < duration {
+                    self.insert((
+                        timestamp.wrapping_add(1),
+                        Event::TempoRamp {
+                            from_bpm,
+                            to_bpm,
+                            duration,
+                            curve,
+                            start,
+                        },
+                    ))?;
+                }
+
+                Ok(())
+            }
+            Event::Slide {
+                channel,
+                slide,
+                start,
+                id,
+            } => {
+                // Another slide or another note took over, or the note was released
+                if self.slide_ids[channel] != id || self.notes[channel].is_none() {
+                    return Ok(());
+                }
+
+                let elapsed = timestamp.wrapping_sub(start);
+                let cents = slide.cents(elapsed);
+                if cents != self.pitches[channel] {
+                    self.pitches[channel] = cents;
+                    self.write_frequency(backend, channel, 0)?;
+                }
+
+                if elapsed < slide.duration {
+                    self.insert((
+                        timestamp.wrapping_add(1),
+                        Event::Slide {
+                            channel,
+                            slide,
+                            start,
+                            id,
+                        },
+                    ))?;
+                } else if let Some(note) = slide.note {
+                    self.notes[channel] = Some(note);
+                }
+
+                Ok(())
+            }
+            Event::Lane {
+                channel,
+                lane,
+                start,
+                id,
+                value,
+            } => {
+                // Another lane for the same parameter took over
+                if self.lane_ids[channel][lane.parameter.index()] != id {
+                    return Ok(());
+                }
+
+                let elapsed = timestamp.wrapping_sub(start);
+                let new_value = lane.value(elapsed);
+                if value != Some(new_value) {
+                    self.set_parameter(backend, channel, lane.parameter, new_value)?;
+                }
+
+                if elapsed < lane.duration {
+                    self.insert((
+                        timestamp.wrapping_add(1),
+                        Event::Lane {
+                            channel,
+                            lane,
+                            start,
+                            id,
+                            value: Some(new_value),
+                        },
+                    ))?;
+                }
+
+                Ok(())
+            }
+            Event::Effect {
+                channel,
+                effect,
+                duration,
+                start,
+                id,
+            } => {
+                let elapsed = timestamp.wrapping_sub(start);
+
+                // Another effect took over or the row is over
+                if self.effect_ids[channel] != id || elapsed >= duration {
+                    if let Effect::Arpeggio { .. } = effect {
+                        self.write_frequency(backend, channel, 0)?;
+                    }
+                    return Ok(());
+                }
+
+                match effect {
+                    Effect::Arpeggio { .. } => self.write_frequency(backend, channel, effect.cents(elapsed))?,
+                    Effect::VolumeSlide { step } => {
+                        if let (Some(velocity), false) = (self.velocities[channel], elapsed == 0) {
+                            let velocity = (velocity as i32 + step as i32).clamp(0, MAX_VELOCITY as i32);
+                            self.set_velocity(backend, channel, velocity as u8)?;
+                        }
+                    }
+                    Effect::Retrigger { interval } => {
+                        if let (Some(note), false) = (self.notes[channel], self.muted) {
+                            if elapsed > 0 && elapsed.checked_rem(interval) == Some(0) {
+                                // The note starts again and is put back at the pitch of its slides and bends
+                                backend.stop_note(channel).map_err(SequenceError::Backend)?;
+                                backend.start_note(channel, note).map_err(SequenceError::Backend)?;
+                                self.write_frequency(backend, channel, 0)?;
+                                observer.on_note_on(channel, note, timestamp.wrapping_add(self.offset));
+                            }
+                        }
+                    }
+                    Effect::NoteCut { ticks } => {
+                        if elapsed == ticks && self.notes[channel].is_some() {
+                            self.run_action(backend, observer, timestamp, Action::NoteOff { channel }, false)?;
+                        }
+                    }
+                    // Delays are taken care of when the notes start
+                    Effect::NoteDelay { .. } => {}
+                }
+
+                self.insert((
+                    timestamp.wrapping_add(1),
+                    Event::Effect {
+                        channel,
+                        effect,
+                        duration,
+                        start,
+                        id,
+                    },
+                ))
+            }
+            Event::Fade {
+                from,
+                to,
+                duration,
+                start,
+                id,
+            } => {
+                // A newer fade took over
+                if self.fade_id != id {
+                    return Ok(());
+                }
+
+                let elapsed = timestamp.wrapping_sub(start);
+                let fade = Curve::Linear.value(from as f32, to as f32, elapsed, duration);
+                let fade = libm::roundf(fade) as u8;
+
+                if fade != self.fade {
+                    self.fade = fade;
+
+                    // While fast forwarding only the fade is kept, the levels are written when the notes are started again
+                    if !self.muted {
+                        self.update_levels(backend)?;
+                    }
+                }
+
+                if elapsed < duration {
+                    self.insert((
+                        timestamp.wrapping_add(1),
+                        Event::Fade {
+                            from,
+                            to,
+                            duration,
+                            start,
+                            id,
+                        },
+                    ))?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    fn run_action<O: Observer<U> + ?Sized>(
+        &mut self,
+        backend: &mut B,
+        observer: &mut O,
+        timestamp: u32,
+        action: Action<B, U>,
+        late: bool,
+    ) -> Result<(), SequenceError<B::Error>> {
+        if late && self.late_policy != LatePolicy::RunLate && action.can_be_dropped() {
+            return Ok(());
+        }
+
+        #[cfg(feature = "rtt")]
+        rtt_target::rprintln!("Running {} at {}", action, timestamp);
+        match action {
+            Action::Custom { function } => function(backend).map_err(SequenceError::Backend)?,
+            Action::SetInstrument {
+                channel,
+                instrument: index,
+            } => {
+                let instrument = self
+                    .instruments
+                    .get(index)
+                    .ok_or(SequenceError::UnknownInstrument { index })?;
+                backend
+                    .set_instrument(channel, instrument.melody.clone())
+                    .map_err(SequenceError::Backend)?;
+
+                if channel < CHANNELS {
+                    self.channel_instruments[channel] = Some(index);
+                    self.levels[channel] = instrument.levels;
+                    self.velocities[channel] = None;
+                }
+            }
+            Action::NoteOn {
+                channel,
+                value,
+                velocity,
+            } => {
+                if let Some(ticks) = self.note_delay(channel, timestamp) {
+                    return self.insert((
+                        timestamp.wrapping_add(ticks),
+                        Event::Action(Action::NoteOn {
+                            channel,
+                            value,
+                            velocity,
+                        }),
+                    ));
+                }
+
+                if let Some(note) = self.notes.get_mut(channel) {
+                    *note = Some(value);
+                    self.pitches[channel] = pitch::cents(value);
+                    self.slide_ids[channel] = self.slide_ids[channel].wrapping_add(1);
+                }
+
+                self.set_velocity(backend, channel, velocity)?;
+                if !self.muted {
+                    backend
+                        .start_note(channel, value)
+                        .map_err(SequenceError::Backend)?;
+                    observer.on_note_on(channel, value, timestamp.wrapping_add(self.offset));
+                }
+            }
+            Action::NoteOff { channel } => {
+                if let Some(note) = self.notes.get_mut(channel) {
+                    *note = None;
+                }
+
+                if !self.muted {
+                    backend.stop_note(channel).map_err(SequenceError::Backend)?;
+                    observer.on_note_off(channel, timestamp.wrapping_add(self.offset));
+                }
+            }
+            Action::PlayNote {
+                channel,
+                value,
+                velocity,
+                duration,
+            } => {
+                let timestamp = timestamp.wrapping_add(self.note_delay(channel, timestamp).unwrap_or(0));
+
+                self.insert((
+                    timestamp,
+                    Event::Action(Action::NoteOn {
+                        channel,
+                        value,
+                        velocity,
+                    }),
+                ))?;
+                self.insert((
+                    timestamp.wrapping_add(duration),
+                    Event::Action(Action::NoteOff { channel }),
+                ))?;
+            }
+            Action::Repetition {
+                pattern,
+                repetition_duration,
+                repetition_times: repetition_count,
+            } => {
+                if let Some(pattern_start) = pattern.start(timestamp) {
+                    self.insert(pattern_start)?;
+                }
+
+                if repetition_count > 1 {
+                    self.insert((
+                        timestamp.wrapping_add(repetition_duration),
+                        Event::Action(Action::Repetition {
+                            pattern,
+                            repetition_duration,
+                            repetition_times: repetition_count - 1,
+                        }),
+                    ))?;
+                }
+            }
+            Action::Loop {
+                pattern,
+                loop_duration,
+            } => {
+                if let Some(pattern_start) = pattern.start(timestamp) {
+                    self.insert(pattern_start)?;
+                }
+
+                self.insert((
+                    timestamp.wrapping_add(loop_duration),
+                    Event::Action(Action::Loop {
+                        pattern,
+                        loop_duration,
+                    }),
+                ))?;
+            }
+            Action::SetTempo { bpm } => self.set_tempo(bpm),
+            Action::PitchBend {
+                channel,
+                cents,
+                duration,
+            } => {
+                if let Some(from) = self.pitches.get(channel) {
+                    let slide = Slide::new(*from, from + cents, duration);
+                    self.start_slide(channel, slide, timestamp)?;
+                }
+            }
+            Action::Portamento {
+                channel,
+                to,
+                duration,
+            } => {
+                if let Some(from) = self.pitches.get(channel) {
+                    let slide = Slide::new(*from, pitch::cents(to), duration).to_note(to);
+                    self.start_slide(channel, slide, timestamp)?;
+                }
+            }
+            Action::Glissando {
+                channel,
+                to,
+                duration,
+            } => {
+                if let Some(from) = self.pitches.get(channel) {
+                    let mut slide = Slide::new(*from, pitch::cents(to), duration).to_note(to);
+                    slide.glissando = true;
+                    self.start_slide(channel, slide, timestamp)?;
+                }
+            }
+            Action::Automate { channel, lane } => {
+                if let Some(lane_ids) = self.lane_ids.get_mut(channel) {
+                    let id = lane_ids[lane.parameter.index()].wrapping_add(1);
+                    lane_ids[lane.parameter.index()] = id;
+
+                    self.insert((
+                        timestamp,
+                        Event::Lane {
+                            channel,
+                            lane,
+                            start: timestamp,
+                            id,
+                            value: None,
+                        },
+                    ))?;
+                }
+            }
+            Action::Effect {
+                channel,
+                effect,
+                duration,
+            } => {
+                if let Some(effect_id) = self.effect_ids.get_mut(channel) {
+                    let id = effect_id.wrapping_add(1);
+                    *effect_id = id;
+
+                    match effect {
+                        Effect::NoteDelay { ticks } => self.note_delays[channel] = Some((timestamp, ticks)),
+                        _ => self.insert((
+                            timestamp,
+                            Event::Effect {
+                                channel,
+                                effect,
+                                duration,
+                                start: timestamp,
+                                id,
+                            },
+                        ))?,
+                    }
+                }
+            }
+            Action::FadeOut { ticks } => {
+                self.fade_id = self.fade_id.wrapping_add(1);
+                self.insert((
+                    timestamp,
+                    Event::Fade {
+                        from: self.fade,
+                        to: 0,
+                        duration: ticks,
+                        start: timestamp,
+                        id: self.fade_id,
+                    },
+                ))?;
+            }
+            Action::FadeIn { ticks } => {
+                self.fade_id = self.fade_id.wrapping_add(1);
+                self.insert((
+                    timestamp,
+                    Event::Fade {
+                        from: 0,
+                        to: MAX_VOLUME,
+                        duration: ticks,
+                        start: timestamp,
+                        id: self.fade_id,
+                    },
+                ))?;
+            }
+            Action::TempoRamp {
+                from_bpm,
+                to_bpm,
+                duration,
+                curve,
+            } => {
+                self.insert((
+                    timestamp,
+                    Event::TempoRamp {
+                        from_bpm,
+                        to_bpm,
+                        duration,
+                        curve,
+                        start: timestamp,
+                    },
+                ))?;
+            }
+            Action::Marker { id } => {
+                self.last_marker = Some(timestamp);
+                observer.on_marker(id, timestamp.wrapping_add(self.offset));
+            }
+            // Jumps are left out while fast forwarding, they're only taken when the sequence really gets there
+            Action::Jump { .. } | Action::ToCoda { .. } if self.muted => {}
+            Action::Jump { target, times } => {
+                if self.count_jump(timestamp, times)? {
+                    self.jump(backend, timestamp, target)?;
+                }
+            }
+            Action::ToCoda { coda } => {
+                if self.jumped {
+                    self.jump(backend, timestamp, JumpTarget::Marker(coda))?;
+                }
+            }
+            Action::User { event } => {
+                if !self.muted {
+                    observer.on_event(&event, timestamp.wrapping_add(self.offset));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets the levels of the channel for the velocity, if the instrument on it has levels and they aren't set for it already.
+    /// While fast forwarding only the velocity is kept, the levels are written by [Self::start_sounding_notes].
+    fn set_velocity(&mut self, backend: &mut B, channel: usize, velocity: u8) -> Result<(), SequenceError<B::Error>> {
+        let levels = match self.levels.get(channel) {
+            Some(Some(levels)) if self.velocities[channel] != Some(velocity) => *levels,
+            _ => return Ok(()),
+        };
+
+        if !self.muted {
+            self.write_levels(backend, channel, levels, velocity)?;
+        }
+        self.velocities[channel] = Some(velocity);
+
+        Ok(())
+    }
+
+    /// Rewrites the levels of every channel that has them after the volume changed
+    fn update_levels(&mut self, backend: &mut B) -> Result<(), SequenceError<B::Error>> {
+        for channel in 0..CHANNELS {
+            if let (Some(levels), Some(velocity)) = (self.levels[channel], self.velocities[channel]) {
+                self.write_levels(backend, channel, levels, velocity)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_levels(&self, backend: &mut B, channel: usize, levels: Levels, velocity: u8) -> Result<(), SequenceError<B::Error>> {
+        // The master volume scales the fade, like a fader after the fader of the score
+        let volume = (self.volume as u32 * self.fade as u32 / MAX_VOLUME as u32) as u8;
+        let (modulator, carrier) = levels.registers(velocity, volume);
+
+        backend
+            .set_levels(channel, modulator, carrier)
+            .map_err(SequenceError::Backend)
+    }
+
+    /// Starts sliding the note on the channel, if there is one.
+    /// A slide that was already going on on the channel is stopped.
+    fn start_slide(&mut self, channel: usize, slide: Slide, timestamp: u32) -> Result<(), SequenceError<B::Error>> {
+        if self.notes[channel].is_none() {
+            return Ok(());
+        }
+
+        let id = self.slide_ids[channel].wrapping_add(1);
+        self.slide_ids[channel] = id;
+
+        self.insert((
+            timestamp,
+            Event::Slide {
+                channel,
+                slide,
+                start: timestamp,
+                id,
+            },
+        ))
+    }
+
+    /// Sets the parameter of a lane on the channel.
+    /// The levels and the feedback can only be changed for an instrument with [Levels].
+    fn set_parameter(&mut self, backend: &mut B, channel: usize, parameter: Parameter, value: i32) -> Result<(), SequenceError<B::Error>> {
+        match parameter {
+            Parameter::ModulatorLevel | Parameter::CarrierLevel => {
+                if let Some(levels) = &mut self.levels[channel] {
+                    if parameter == Parameter::ModulatorLevel {
+                        levels.modulator = value as u8;
+                    } else {
+                        levels.carrier = value as u8;
+                    }
+                    let levels = *levels;
+
+                    // Otherwise they're written with the next note, or when the notes are started again after fast forwarding
+                    if let (Some(velocity), false) = (self.velocities[channel], self.muted) {
+                        self.write_levels(backend, channel, levels, velocity)?;
+                    }
+                }
+            }
+            Parameter::Feedback => {
+                if let (Some(levels), false) = (self.levels[channel], self.muted) {
+                    backend
+                        .set_feedback(channel, value as u8, levels.additive)
+                        .map_err(SequenceError::Backend)?;
+                }
+            }
+            Parameter::Pitch => {
+                self.pitch_offsets[channel] = value;
+                self.write_frequency(backend, channel, 0)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the pitch of the note on the channel with the extra cents on top, if a note is sounding
+    fn write_frequency(&self, backend: &mut B, channel: usize, cents: i32) -> Result<(), SequenceError<B::Error>> {
+        if self.notes[channel].is_none() || self.muted {
+            return Ok(());
+        }
+
+        // Only sounding notes are changed, so the key stays on
+        let (block, f_number) = pitch::frequency(self.pitches[channel] + self.pitch_offsets[channel] + cents);
+        backend
+            .set_frequency(channel, block, f_number, true)
+            .map_err(SequenceError::Backend)
+    }
+
+    /// The ticks that the notes starting on the channel at the timestamp are delayed by an [Effect::NoteDelay]
+    fn note_delay(&self, channel: usize, timestamp: u32) -> Option<u32> {
+        match self.note_delays.get(channel) {
+            Some(Some((start, ticks))) if *start == timestamp && *ticks > 0 => Some(*ticks),
+            _ => None,
+        }
+    }
+
+    fn set_tempo(&mut self, bpm: u32) {
+        // The clock can't run at 0 bpm
+        let bpm = bpm.max(1);
+
+        if self.tempo != Some(bpm) {
+            self.tempo = Some(bpm);
+            self.tempo_changed = true;
+        }
+    }
+
+    fn insert(&mut self, (timestamp, event): (u32, Event<B, U>)) -> Result<(), SequenceError<B::Error>> {
+        self.queue
+            .push(timestamp, event)
+            .map_err(|_| SequenceError::QueueFull)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceError<E> {
+    /// The backend returned an error
+    Backend(E),
+    /// There was no room left in the queue of the sequence
+    QueueFull,
+    /// An event should have run before the timestamp the sequence was run with.
+    /// Only returned with [LatePolicy::Error].
+    LateEvent { scheduled: u32, timestamp: u32 },
+    /// A jump went to a marker that isn't in the sequence
+    UnknownMarker { id: u32 },
+    /// There are more than [MAX_JUMPS] jumps in the sequence
+    TooManyJumps,
+    /// An [Action::SetInstrument] refers to an instrument that isn't in the instrument table
+    UnknownInstrument { index: usize },
+    /// An action uses a channel that doesn't exist. Only returned by [Sequence::validate].
+    InvalidChannel { channel: usize },
+    /// An [Action::SetTempo] or [Action::TempoRamp] goes to 0 bpm. Only returned by [Sequence::validate],
+    /// when the sequence is played the tempo is kept at 1 bpm instead.
+    InvalidTempo,
+}
+
+/// Calls the function for the action and for the actions in its pattern
+fn visit_action<B: SynthBackend, U>(action: &Action<B, U>, f: &mut impl FnMut(&Action<B, U>)) {
+    f(action);
+
+    if let Action::Repetition { pattern, .. } | Action::Loop { pattern, .. } = action {
+        for point in pattern.points.iter() {
+            visit_action(&point.value, f);
+        }
+    }
+}
+
+/// What a [Sequence] does with events that it comes across too late.
+///
+/// This happens when the sequence is not run for every timestamp,
+/// for example because an interrupt was missed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LatePolicy {
+    /// Run the late events right away
+    RunLate,
+    /// Skip the late events that start something (notes, custom actions and user events).
+    /// Note offs and the structure of the sequence (patterns and repetitions) still run,
+    /// so no notes are left hanging and the rest of the sequence plays as normal.
+    Drop,
+    /// Stop and return [SequenceError::LateEvent].
+    /// The late event is handled like with [LatePolicy::Drop] first, so the sequence can be run again to go on after it.
+    Error,
+}
+
+/// An entry of the instrument table of a sequence
+pub struct Instrument {
+    pub melody: MelodyInstrument,
+    /// The levels the instrument is set up with, needed to play it with a velocity
+    pub levels: Option<Levels>,
+}
+
+impl Instrument {
+    /// An instrument that ignores velocity
+    pub fn new(melody: MelodyInstrument) -> Self {
+        Self {
+            melody,
+            levels: None,
+        }
+    }
+
+    /// Lets the instrument be played with a velocity. The levels must match the ones in the melody instrument.
+    pub fn with_levels(mut self, levels: Levels) -> Self {
+        self.levels = Some(levels);
+        self
+    }
+}
+
+impl From<MelodyInstrument> for Instrument {
+    fn from(melody: MelodyInstrument) -> Self {
+        Self::new(melody)
+    }
+}
+
+impl Clone for Instrument {
+    fn clone(&self) -> Self {
+        Self {
+            melody: self.melody.clone(),
+            levels: self.levels,
+        }
+    }
+}
+
+pub struct ActionPoint<B: SynthBackend, U = ()> {
+    delay: u32,
+    value: Action<B, U>,
+}
+
+impl<B: SynthBackend, U> ActionPoint<B, U> {
+    pub fn new(delay: u32, value: Action<B, U>) -> Self {
+        Self { delay, value }
+    }
+}
+
+struct AbsoluteActionPoint<B: SynthBackend, U> {
+    timestamp: u32,
+    value: Action<B, U>,
+}
+
+impl<B: SynthBackend, U> AbsoluteActionPoint<B, U> {
+    fn new(timestamp: u32, value: Action<B, U>) -> Self {
+        Self { timestamp, value }
+    }
+}
+
+/// A fixed list of actions that can be played (repeatedly) by a sequence.
+///
+/// Cloning a pattern doesn't copy the actions.
+pub struct Pattern<B: SynthBackend, U = ()> {
+    points: Arc<[AbsoluteActionPoint<B, U>]>,
+}
+
+impl<B: SynthBackend, U: Clone> Pattern<B, U> {
+    pub fn new(relative_points: &[ActionPoint<B, U>]) -> Self {
+        let mut running_timestamp = 0;
+
+        let points: Vec<_> = relative_points
+            .iter()
+            .map(|point| {
+                running_timestamp += point.delay;
+                AbsoluteActionPoint::new(running_timestamp, point.value.clone())
+            })
+            .collect();
+
+        Self {
+            points: points.into(),
+        }
+    }
+
+    /// The event that plays the first point of the pattern when the pattern starts at the given timestamp
+    fn start(&self, start: u32) -> Option<(u32, Event<B, U>)> {
+        self.event(0, start)
+    }
+
+    /// The event that plays the point after the point at the index
+    fn next(&self, index: usize, start: u32) -> Option<(u32, Event<B, U>)> {
+        self.event(index + 1, start)
+    }
+
+    fn event(&self, index: usize, start: u32) -> Option<(u32, Event<B, U>)> {
+        self.points.get(index).map(|point| {
+            (
+                start.wrapping_add(point.timestamp),
+                Event::Pattern {
+                    pattern: self.clone(),
+                    index,
+                    start,
+                },
+            )
+        })
+    }
+}
+
+/// Where an [Action::Jump] goes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JumpTarget {
+    /// The start of the sequence, da capo
+    Start,
+    /// The first [Action::Marker] with the id, like a segno
+    Marker(u32),
+}
+
+/// A change of pitch from one amount of cents to another
+#[derive(Clone, Copy)]
+struct Slide {
+    from: i32,
+    to: i32,
+    duration: u32,
+    /// Go in steps of a semitone instead of smoothly
+    glissando: bool,
+    /// The note the channel plays at the end of the slide, if it's a new one
+    note: Option<Note>,
+}
+
+impl Slide {
+    fn new(from: i32, to: i32, duration: u32) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            glissando: false,
+            note: None,
+        }
+    }
+
+    fn to_note(mut self, note: Note) -> Self {
+        self.note = Some(note);
+        self
+    }
+
+    /// The pitch at the amount of ticks into the slide
+    fn cents(&self, elapsed: u32) -> i32 {
+        let cents = libm::roundf(Curve::Linear.value(self.from as f32, self.to as f32, elapsed, self.duration)) as i32;
+
+        if self.glissando && elapsed < self.duration {
+            self.from + (cents - self.from) / SEMITONE * SEMITONE
+        } else {
+            cents
+        }
+    }
+}
+
+/// The things that live in the queue of a sequence
+/// The state of a sequence right before one of its markers
+struct Snapshot<B: SynthBackend, const N: usize, U> {
+    marker: u32,
+    queue: EventQueue<Event<B, U>, N>,
+    tempo: Option<u32>,
+    last_marker: Option<u32>,
+    notes: [Option<Note>; CHANNELS],
+    channel_instruments: [Option<usize>; CHANNELS],
+    levels: [Option<Levels>; CHANNELS],
+    velocities: [Option<u8>; CHANNELS],
+    fade: u8,
+    fade_id: u32,
+    pitches: [i32; CHANNELS],
+    slide_ids: [u32; CHANNELS],
+    pitch_offsets: [i32; CHANNELS],
+    lane_ids: [[u32; Parameter::COUNT]; CHANNELS],
+    effect_ids: [u32; CHANNELS],
+    note_delays: [Option<(u32, u32)>; CHANNELS],
+}
+
+enum Event<B: SynthBackend, U> {
+    Action(Action<B, U>),
+    /// Runs the point of the pattern at the index.
+    /// The pattern was started at the start timestamp.
+    Pattern {
+        pattern: Pattern<B, U>,
+        index: usize,
+        start: u32,
+    },
+    /// Sets the tempo for the current tick of a ramp that was started at the start timestamp
+    /// and schedules the next tick
+    TempoRamp {
+        from_bpm: u32,
+        to_bpm: u32,
+        duration: u32,
+        curve: Curve,
+        start: u32,
+    },
+    /// Sets the pitch of the channel for the current tick of a slide that was started at the start timestamp
+    /// and schedules the next tick, as long as the slide with the id is the last thing that started on the channel
+    Slide {
+        channel: usize,
+        slide: Slide,
+        start: u32,
+        id: u32,
+    },
+    /// Sets the parameter of the channel for the current tick of a lane that was started at the start timestamp
+    /// and schedules the next tick, as long as the lane with the id is the last one for the parameter.
+    /// The value is what the lane set the tick before, so it's only written when it changes.
+    Lane {
+        channel: usize,
+        lane: Lane,
+        start: u32,
+        id: u32,
+        value: Option<i32>,
+    },
+    /// Runs the current tick of an effect that was started at the start timestamp and schedules the next tick,
+    /// as long as the effect with the id is the last one of the channel
+    Effect {
+        channel: usize,
+        effect: Effect,
+        duration: u32,
+        start: u32,
+        id: u32,
+    },
+    /// Sets the fade volume for the current tick of a fade that was started at the start timestamp
+    /// and schedules the next tick, unless a newer fade with another id started
+    Fade {
+        from: u8,
+        to: u8,
+        duration: u32,
+        start: u32,
+        id: u32,
+    },
+}
+
+impl<B: SynthBackend, U> Event<B, U> {
+    /// The id of the marker the event runs, if it runs one
+    fn marker_id(&self) -> Option<u32> {
+        let action = match self {
+            Event::Action(action) => action,
+            Event::Pattern { pattern, index, .. } => &pattern.points[*index].value,
+            Event::TempoRamp { .. }
+            | Event::Fade { .. }
+            | Event::Slide { .. }
+            | Event::Lane { .. }
+            | Event::Effect { .. } => return None,
+        };
+
+        match action {
+            Action::Marker { id } => Some(*id),
+            _ => None,
+        }
+    }
+}
+
+pub enum Action<B: SynthBackend, U = ()> {
+    Custom {
+        function: fn(&mut B) -> Result<(), B::Error>,
+    },
+    /// Sets up the channel with the instrument at the index in the instrument table of the sequence.
+    /// See [Sequence::with_instruments].
+    SetInstrument {
+        channel: usize,
+        instrument: usize,
+    },
+    /// Starts the note. The velocity is only applied if the instrument on the channel came from the instrument table
+    /// and has [Levels], see [Instrument::with_levels].
+    NoteOn {
+        channel: usize,
+        value: Note,
+        velocity: u8,
+    },
+    NoteOff {
+        channel: usize,
+    },
+    PlayNote {
+        channel: usize,
+        value: Note,
+        velocity: u8,
+        duration: u32,
+    },
+    Repetition {
+        pattern: Pattern<B, U>,
+        repetition_duration: u32,
+        repetition_times: u32,
+    },
+    /// Plays the pattern every loop duration, forever.
+    /// The sequence never ends after this, so put the intro of a song before it and the body that loops in it.
+    Loop {
+        pattern: Pattern<B, U>,
+        loop_duration: u32,
+    },
+    /// Changes the tempo of the sequence to the given beats per minute.
+    /// See [Sequence::take_tempo_change].
+    SetTempo {
+        bpm: u32,
+    },
+    /// Gradually changes the tempo from one bpm to the other over the duration in ticks.
+    /// Use this for accelerandos and ritardandos.
+    /// The tempo is updated every tick and the changes can be taken with [Sequence::take_tempo_change].
+    TempoRamp {
+        from_bpm: u32,
+        to_bpm: u32,
+        duration: u32,
+        curve: Curve,
+    },
+    /// Fades the sequence out from where it is to silence over the ticks.
+    /// The sequence stays silent until an [Action::FadeIn], and the notes are still played, so end it with note offs.
+    ///
+    /// Fades work on top of the master volume and only on the channels that have an instrument with [Levels].
+    FadeOut {
+        ticks: u32,
+    },
+    /// Fades the sequence in from silence over the ticks. Put it before the first notes to fade in at the start.
+    FadeIn {
+        ticks: u32,
+    },
+    /// Bends the note on the channel by the amount of cents (a hundredth of a semitone) over the duration in ticks.
+    /// The bend starts from where the pitch is now, so bends add up. A duration of 0 bends right away.
+    /// The next note on the channel is played at its own pitch again.
+    PitchBend {
+        channel: usize,
+        cents: i32,
+        duration: u32,
+    },
+    /// Slides the note on the channel smoothly to the other note over the duration in ticks, without starting it again.
+    /// Afterwards the channel counts as playing the new note.
+    Portamento {
+        channel: usize,
+        to: Note,
+        duration: u32,
+    },
+    /// Like [Action::Portamento], but in steps of a semitone
+    Glissando {
+        channel: usize,
+        to: Note,
+        duration: u32,
+    },
+    /// Starts an automation lane on the channel. It replaces the lane that was running for the same parameter.
+    /// The levels and the feedback can only be automated on an instrument with [Levels].
+    Automate {
+        channel: usize,
+        lane: Lane,
+    },
+    /// Runs the tracker effect on the notes of the channel every tick for the duration in ticks, like a row in a tracker.
+    /// It replaces the effect that was running on the channel.
+    Effect {
+        channel: usize,
+        effect: Effect,
+        duration: u32,
+    },
+    /// Marks a point in the sequence that can be returned to (see [EndBehaviour::LoopFromMarker](crate::player::EndBehaviour::LoopFromMarker))
+    /// and is reported to the [Observer] with its id.
+    Marker {
+        id: u32,
+    },
+    /// Jumps to the target if this jump was taken less than the given amount of times before.
+    /// Use it with `times: 1` for a D.C. or D.S. and with more for repeats.
+    ///
+    /// The notes that are playing are released and the notes that would be playing at the target are started.
+    /// Events at the same timestamp that come after the jump don't run.
+    ///
+    /// The sequence keeps a snapshot of the first [MAX_MARKER_SNAPSHOTS] markers it passes, so jumping back to them is quick.
+    /// To get to any other target, the sequence runs everything from the start up to it without making a sound,
+    /// so the further that target is into the song, the longer the jump takes. Keep that in mind for long songs that are played from an interrupt.
+    Jump {
+        target: JumpTarget,
+        times: u32,
+    },
+    /// Jumps to the marker with the coda id, but only after the sequence has taken a jump before (al Coda)
+    ToCoda {
+        coda: u32,
+    },
+    /// Passes the event to [Observer::on_event], so the application can react to the song with its own data.
+    /// Like the other things the observer is told about, it's not reported while fast forwarding.
+    User {
+        event: U,
+    },
+}
+
+impl<B: SynthBackend, U> Action<B, U> {
+    /// The channel the action plays on, if it's one that plays on a channel.
+    /// A [Action::Custom] can do anything, so it doesn't have one.
+    pub fn channel(&self) -> Option<usize> {
+        match *self {
+            Action::SetInstrument { channel, .. }
+            | Action::NoteOn { channel, .. }
+            | Action::NoteOff { channel }
+            | Action::PlayNote { channel, .. }
+            | Action::PitchBend { channel, .. }
+            | Action::Portamento { channel, .. }
+            | Action::Glissando { channel, .. }
+            | Action::Automate { channel, .. }
+            | Action::Effect { channel, .. } => Some(channel),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the action may be skipped when it's late
+    fn can_be_dropped(&self) -> bool {
+        matches!(
+            self,
+            Action::Custom { .. }
+                | Action::NoteOn { .. }
+                | Action::PlayNote { .. }
+                | Action::User { .. }
+        )
+    }
+}
+
+impl<B: SynthBackend, U> Display for Action<B, U> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Action::Custom { .. } => write!(f, "Action Custom"),
+            Action::SetInstrument { .. } => write!(f, "Action SetInstrument"),
+            Action::NoteOn { .. } => write!(f, "Action NoteOn"),
+            Action::NoteOff { .. } => write!(f, "Action NoteOff"),
+            Action::PlayNote { .. } => write!(f, "Action PlayNote"),
+            Action::Repetition { .. } => write!(f, "Action Repetition"),
+            Action::Loop { .. } => write!(f, "Action Loop"),
+            Action::SetTempo { .. } => write!(f, "Action SetTempo"),
+            Action::TempoRamp { .. } => write!(f, "Action TempoRamp"),
+            Action::FadeOut { .. } => write!(f, "Action FadeOut"),
+            Action::FadeIn { .. } => write!(f, "Action FadeIn"),
+            Action::PitchBend { .. } => write!(f, "Action PitchBend"),
+            Action::Portamento { .. } => write!(f, "Action Portamento"),
+            Action::Glissando { .. } => write!(f, "Action Glissando"),
+            Action::Automate { .. } => write!(f, "Action Automate"),
+            Action::Effect { .. } => write!(f, "Action Effect"),
+            Action::Marker { id } => write!(f, "Action Marker {}", id),
+            Action::Jump { .. } => write!(f, "Action Jump"),
+            Action::ToCoda { .. } => write!(f, "Action ToCoda"),
+            Action::User { .. } => write!(f, "Action User"),
+        }
+    }
+}
+
+impl<B: SynthBackend, const N: usize, U: Clone> Clone for Sequence<B, N, U> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue.clone(),
+            start: self.start.clone(),
+            offset: self.offset,
+            jump_counts: self.jump_counts.clone(),
+            jumped: self.jumped,
+            late_policy: self.late_policy,
+            late_events: self.late_events,
+            tempo: self.tempo,
+            tempo_changed: self.tempo_changed,
+            notes: self.notes,
+            muted: self.muted,
+            last_marker: self.last_marker,
+            instruments: self.instruments.clone(),
+            channel_instruments: self.channel_instruments,
+            levels: self.levels,
+            velocities: self.velocities,
+            volume: self.volume,
+            fade: self.fade,
+            fade_id: self.fade_id,
+            pitches: self.pitches,
+            slide_ids: self.slide_ids,
+            pitch_offsets: self.pitch_offsets,
+            lane_ids: self.lane_ids,
+            effect_ids: self.effect_ids,
+            note_delays: self.note_delays,
+            snapshots: self.snapshots.clone(),
+        }
+    }
+}
+
+impl<B: SynthBackend, const N: usize, U: Clone> Clone for Snapshot<B, N, U> {
+    fn clone(&self) -> Self {
+        Self {
+            marker: self.marker,
+            queue: self.queue.clone(),
+            tempo: self.tempo,
+            last_marker: self.last_marker,
+            notes: self.notes,
+            channel_instruments: self.channel_instruments,
+            levels: self.levels,
+            velocities: self.velocities,
+            fade: self.fade,
+            fade_id: self.fade_id,
+            pitches: self.pitches,
+            slide_ids: self.slide_ids,
+            pitch_offsets: self.pitch_offsets,
+            lane_ids: self.lane_ids,
+            effect_ids: self.effect_ids,
+            note_delays: self.note_delays,
+        }
+    }
+}
+
+impl<B: SynthBackend, U> Clone for Pattern<B, U> {
+    fn clone(&self) -> Self {
+        Self {
+            points: self.points.clone(),
+        }
+    }
+}
+
+impl<B: SynthBackend, U: Clone> Clone for Event<B, U> {
+    fn clone(&self) -> Self {
+        match self {
+            Event::Action(action) => Event::Action(action.clone()),
+            Event::Pattern {
+                pattern,
+                index,
+                start,
+            } => Event::Pattern {
+                pattern: pattern.clone(),
+                index: *index,
+                start: *start,
+            },
+            Event::TempoRamp {
+                from_bpm,
+                to_bpm,
+                duration,
+                curve,
+                start,
+            } => Event::TempoRamp {
+                from_bpm: *from_bpm,
+                to_bpm: *to_bpm,
+                duration: *duration,
+                curve: *curve,
+                start: *start,
+            },
+            Event::Slide {
+                channel,
+                slide,
+                start,
+                id,
+            } => Event::Slide {
+                channel: *channel,
+                slide: *slide,
+                start: *start,
+                id: *id,
+            },
+            Event::Lane {
+                channel,
+                lane,
+                start,
+                id,
+                value,
+            } => Event::Lane {
+                channel: *channel,
+                lane: *lane,
+                start: *start,
+                id: *id,
+                value: *value,
+            },
+            Event::Effect {
+                channel,
+                effect,
+                duration,
+                start,
+                id,
+            } => Event::Effect {
+                channel: *channel,
+                effect: *effect,
+                duration: *duration,
+                start: *start,
+                id: *id,
+            },
+            Event::Fade {
+                from,
+                to,
+                duration,
+                start,
+                id,
+            } => Event::Fade {
+                from: *from,
+                to: *to,
+                duration: *duration,
+                start: *start,
+                id: *id,
+            },
+        }
+    }
+}
+
+impl<B: SynthBackend, U: Clone> Clone for Action<B, U> {
+    fn clone(&self) -> Self {
+        match self {
+            Action::Custom { function } => Action::Custom {
+                function: *function,
+            },
+            Action::SetInstrument {
+                channel,
+                instrument,
+            } => Action::SetInstrument {
+                channel: *channel,
+                instrument: *instrument,
+            },
+            Action::NoteOn {
+                channel,
+                value,
+                velocity,
+            } => Action::NoteOn {
+                channel: *channel,
+                value: *value,
+                velocity: *velocity,
+            },
+            Action::NoteOff { channel } => Action::NoteOff { channel: *channel },
+            Action::PlayNote {
+                channel,
+                value,
+                velocity,
+                duration,
+            } => Action::PlayNote {
+                channel: *channel,
+                value: *value,
+                velocity: *velocity,
+                duration: *duration,
+            },
+            Action::Repetition {
+                pattern,
+                repetition_duration,
+                repetition_times: repetition_count,
+            } => Action::Repetition {
+                pattern: pattern.clone(),
+                repetition_duration: *repetition_duration,
+                repetition_times: *repetition_count,
+            },
+            Action::Loop {
+                pattern,
+                loop_duration,
+            } => Action::Loop {
+                pattern: pattern.clone(),
+                loop_duration: *loop_duration,
+            },
+            Action::SetTempo { bpm } => Action::SetTempo { bpm: *bpm },
+            Action::TempoRamp {
+                from_bpm,
+                to_bpm,
+                duration,
+                curve,
+            } => Action::TempoRamp {
+                from_bpm: *from_bpm,
+                to_bpm: *to_bpm,
+                duration: *duration,
+                curve: *curve,
+            },
+            Action::FadeOut { ticks } => Action::FadeOut { ticks: *ticks },
+            Action::FadeIn { ticks } => Action::FadeIn { ticks: *ticks },
+            Action::PitchBend {
+                channel,
+                cents,
+                duration,
+            } => Action::PitchBend {
+                channel: *channel,
+                cents: *cents,
+                duration: *duration,
+            },
+            Action::Portamento {
+                channel,
+                to,
+                duration,
+            } => Action::Portamento {
+                channel: *channel,
+                to: *to,
+                duration: *duration,
+            },
+            Action::Glissando {
+                channel,
+                to,
+                duration,
+            } => Action::Glissando {
+                channel: *channel,
+                to: *to,
+                duration: *duration,
+            },
+            Action::Automate { channel, lane } => Action::Automate {
+                channel: *channel,
+                lane: *lane,
+            },
+            Action::Effect {
+                channel,
+                effect,
+                duration,
+            } => Action::Effect {
+                channel: *channel,
+                effect: *effect,
+                duration: *duration,
+            },
+            Action::Marker { id } => Action::Marker { id: *id },
+            Action::Jump { target, times } => Action::Jump {
+                target: *target,
+                times: *times,
+            },
+            Action::ToCoda { coda } => Action::ToCoda { coda: *coda },
+            Action::User { event } => Action::User {
+                event: event.clone(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automation::{Shape, Waveform};
+    use crate::dynamics::{MAX_VELOCITY, MP};
+    use crate::mission_impossible::bass_instrument;
+    use crate::mock::{KeyEvent, MockInterface};
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use opl_driver::hl::{Melody, Opl2};
+
+    type Opl = Opl2<MockInterface, Melody>;
+    type TestSequence = Sequence<Opl, 16>;
+
+    fn play_note(channel: usize, duration: u32) -> Action<Opl> {
+        Action::PlayNote {
+            channel,
+            value: Note::A(4),
+            velocity: MAX_VELOCITY,
+            duration,
+        }
+    }
+
+    fn note_on(channel: usize) -> Action<Opl> {
+        Action::NoteOn {
+            channel,
+            value: Note::A(4),
+            velocity: MAX_VELOCITY,
+        }
+    }
+
+    /// Takes all events out of the queue and returns their timestamps and channels
+    fn drain(sequence: &mut TestSequence) -> Vec<(u32, usize)> {
+        core::iter::from_fn(|| sequence.queue.pop())
+            .map(|(timestamp, event)| match event {
+                Event::Action(Action::NoteOn { channel, .. })
+                | Event::Action(Action::NoteOff { channel }) => (timestamp, channel),
+                _ => panic!("Unexpected event"),
+            })
+            .collect()
+    }
+
+    /// Runs the sequence tick by tick until it's done and returns the key events it caused.
+    ///
+    /// The order of events on different channels within the same tick doesn't matter,
+    /// so the events are sorted by tick and channel.
+    fn play<const N: usize>(mut sequence: Sequence<Opl, N>, max_ticks: u32) -> Vec<KeyEvent> {
+        let (mut opl, mock) = MockInterface::opl();
+
+        for tick in 0..max_ticks {
+            mock.set_tick(tick);
+            if !sequence.run(&mut opl, tick).unwrap() {
+                let mut events = mock.key_events();
+                events.sort_by_key(|e| (e.tick, e.channel));
+                return events;
+            }
+        }
+
+        panic!("Sequence didn't finish within {} ticks", max_ticks);
+    }
+
+    #[test]
+    fn new_accumulates_delays() {
+        let sequence = TestSequence::new(&[
+            ActionPoint::new(0, note_on(0)),
+            ActionPoint::new(5, note_on(1)),
+            ActionPoint::new(0, note_on(2)),
+            ActionPoint::new(10, note_on(3)),
+        ]);
+
+        assert_eq!(
+            play(sequence, 100),
+            vec![KeyEvent::on(0, 0), KeyEvent::on(5, 1), KeyEvent::on(5, 2), KeyEvent::on(15, 3)]
+        );
+    }
+
+    #[test]
+    fn insert_keeps_order_and_is_stable() {
+        let mut sequence = TestSequence::new(&[]);
+
+        for (timestamp, channel) in [(10, 0), (20, 1), (30, 2), (10, 3), (0, 4), (20, 5)].iter() {
+            sequence
+                .insert((*timestamp, Event::Action(Action::NoteOff { channel: *channel })))
+                .unwrap();
+        }
+
+        assert_eq!(
+            drain(&mut sequence),
+            vec![(0, 4), (10, 0), (10, 3), (20, 1), (20, 5), (30, 2)]
+        );
+    }
+
+    #[test]
+    fn insert_into_a_full_sequence_fails() {
+        let mut sequence = Sequence::<Opl, 1>::new(&[ActionPoint::new(0, note_on(0))]);
+
+        assert!(matches!(
+            sequence.insert((0, Event::Action(Action::NoteOff { channel: 0 }))),
+            Err(SequenceError::QueueFull)
+        ));
+    }
+
+    #[test]
+    fn merge_interleaves_both_sequences() {
+        let mut sequence = TestSequence::new(&[
+            ActionPoint::new(0, note_on(0)),
+            ActionPoint::new(10, note_on(1)),
+        ]);
+        sequence
+            .merge(TestSequence::new(&[
+                ActionPoint::new(5, note_on(2)),
+                ActionPoint::new(5, note_on(3)),
+                ActionPoint::new(5, note_on(4)),
+            ]))
+            .unwrap();
+
+        assert_eq!(
+            play(sequence, 100),
+            vec![KeyEvent::on(0, 0), KeyEvent::on(5, 2), KeyEvent::on(10, 1), KeyEvent::on(10, 3), KeyEvent::on(15, 4)]
+        );
+    }
+
+    #[test]
+    fn patterns_take_a_single_place_in_the_queue() {
+        let points: Vec<_> = (0..32)
+            .map(|_| ActionPoint::new(1, play_note(0, 1)))
+            .collect();
+
+        // One place for the pattern, one for the note on and two for the note offs of the current and the previous note
+        let sequence = Sequence::<Opl, 4>::new(&points);
+
+        assert_eq!(play(sequence, 100).len(), 64);
+    }
+
+    #[test]
+    fn play_note_is_expanded_to_note_on_and_off() {
+        let sequence = TestSequence::new(&[
+            ActionPoint::new(2, play_note(1, 3)),
+            ActionPoint::new(1, play_note(2, 10)),
+        ]);
+
+        assert_eq!(
+            play(sequence, 100),
+            vec![KeyEvent::on(2, 1), KeyEvent::on(3, 2), KeyEvent::off(5, 1), KeyEvent::off(13, 2)]
+        );
+    }
+
+    #[test]
+    fn note_off_is_written_on_time() {
+        let mut sequence = TestSequence::new(&[
+            ActionPoint::new(
+                0,
+                Action::NoteOn {
+                    channel: 0,
+                    value: Note::C(4),
+                    velocity: MAX_VELOCITY,
+                },
+            ),
+            ActionPoint::new(7, Action::NoteOff { channel: 0 }),
+        ]);
+
+        let (mut opl, mock) = MockInterface::opl();
+
+        mock.set_tick(0);
+        assert!(sequence.run(&mut opl, 0).unwrap());
+        assert!(!mock.writes().is_empty());
+        assert!(mock.writes().iter().all(|w| w.tick == 0));
+
+        for tick in 1..7 {
+            mock.set_tick(tick);
+            assert!(sequence.run(&mut opl, tick).unwrap());
+        }
+
+        mock.set_tick(7);
+        assert!(!sequence.run(&mut opl, 7).unwrap());
+        assert_eq!(mock.key_events(), vec![KeyEvent::on(0, 0), KeyEvent::off(7, 0)]);
+    }
+
+    #[test]
+    fn repetition_repeats_the_sequence() {
+        let sequence = TestSequence::new(&[ActionPoint::new(
+            1,
+            Action::Repetition {
+                pattern: Pattern::new(&[
+                    ActionPoint::new(0, play_note(0, 2)),
+                    ActionPoint::new(3, play_note(0, 2)),
+                ]),
+                repetition_duration: 10,
+                repetition_times: 3,
+            },
+        )]);
+
+        assert_eq!(
+            play(sequence, 100),
+            vec![
+                KeyEvent::on(1, 0),
+                KeyEvent::off(3, 0),
+                KeyEvent::on(4, 0),
+                KeyEvent::off(6, 0),
+                KeyEvent::on(11, 0),
+                KeyEvent::off(13, 0),
+                KeyEvent::on(14, 0),
+                KeyEvent::off(16, 0),
+                KeyEvent::on(21, 0),
+                KeyEvent::off(23, 0),
+                KeyEvent::on(24, 0),
+                KeyEvent::off(26, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_repetitions_are_offset_by_their_parent() {
+        let inner = Action::Repetition {
+            pattern: Pattern::new(&[ActionPoint::new(0, play_note(1, 1))]),
+            repetition_duration: 2,
+            repetition_times: 2,
+        };
+
+        let sequence = TestSequence::new(&[ActionPoint::new(
+            5,
+            Action::Repetition {
+                pattern: Pattern::new(&[
+                    ActionPoint::new(0, play_note(0, 1)),
+                    ActionPoint::new(1, inner),
+                ]),
+                repetition_duration: 10,
+                repetition_times: 2,
+            },
+        )]);
+
+        assert_eq!(
+            play(sequence, 100),
+            vec![
+                KeyEvent::on(5, 0),
+                KeyEvent::off(6, 0),
+                KeyEvent::on(6, 1),
+                KeyEvent::off(7, 1),
+                KeyEvent::on(8, 1),
+                KeyEvent::off(9, 1),
+                KeyEvent::on(15, 0),
+                KeyEvent::off(16, 0),
+                KeyEvent::on(16, 1),
+                KeyEvent::off(17, 1),
+                KeyEvent::on(18, 1),
+                KeyEvent::off(19, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn loop_repeats_forever_without_copying_the_pattern() {
+        let pattern = Pattern::new(&[
+            ActionPoint::new(0, play_note(1, 2)),
+            ActionPoint::new(3, play_note(1, 2)),
+        ]);
+        let mut sequence = TestSequence::new(&[
+            ActionPoint::new(0, play_note(0, 4)),
+            ActionPoint::new(
+                4,
+                Action::Loop {
+                    pattern: pattern.clone(),
+                    loop_duration: 8,
+                },
+            ),
+        ]);
+
+        let (mut opl, mock) = MockInterface::opl();
+        for tick in 0..1000 {
+            mock.set_tick(tick);
+            assert!(sequence.run(&mut opl, tick).unwrap());
+
+            // Ours, the one in the score, the one in the queued loop action and the one of the running pattern
+            assert!(Arc::strong_count(&pattern.points) <= 4);
+        }
+
+        let events = mock.key_events();
+        assert_eq!(
+            &events[..7],
+            &[KeyEvent::on(0, 0), KeyEvent::off(4, 0), KeyEvent::on(4, 1), KeyEvent::off(6, 1), KeyEvent::on(7, 1), KeyEvent::off(9, 1), KeyEvent::on(12, 1)]
+        );
+        assert_eq!(events.iter().filter(|e| e.on && e.channel == 1).count(), 250);
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<(&'static str, u32, u32)>,
+    }
+
+    impl Observer for Recorder {
+        fn on_marker(&mut self, id: u32, timestamp: u32) {
+            self.events.push(("marker", id, timestamp));
+        }
+
+        fn on_note_on(&mut self, channel: usize, _note: Note, timestamp: u32) {
+            self.events.push(("on", channel as u32, timestamp));
+        }
+
+        fn on_note_off(&mut self, channel: usize, timestamp: u32) {
+            self.events.push(("off", channel as u32, timestamp));
+        }
+
+        fn on_end(&mut self, timestamp: u32) {
+            self.events.push(("end", 0, timestamp));
+        }
+    }
+
+    #[test]
+    fn the_observer_is_told_about_markers_notes_and_the_end() {
+        let (mut opl, _) = MockInterface::opl();
+        let mut sequence = TestSequence::new(&[
+            ActionPoint::new(1, Action::Marker { id: 7 }),
+            ActionPoint::new(0, play_note(2, 3)),
+            ActionPoint::new(5, Action::Marker { id: 8 }),
+        ]);
+
+        let mut recorder = Recorder::default();
+        let mut tick = 0;
+        while sequence.run_observed(&mut opl, tick, &mut recorder).unwrap() {
+            tick += 1;
+        }
+
+        assert_eq!(
+            recorder.events,
+            vec![
+                ("marker", 7, 1),
+                ("on", 2, 1),
+                ("off", 2, 4),
+                ("marker", 8, 6),
+                ("end", 0, 6)
+            ]
+        );
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Lamp {
+        Red,
+        Green,
+    }
+
+    #[derive(Default)]
+    struct LampSwitch {
+        lit: Vec<(Lamp, u32)>,
+    }
+
+    impl Observer<Lamp> for LampSwitch {
+        fn on_event(&mut self, lamp: &Lamp, timestamp: u32) {
+            self.lit.push((*lamp, timestamp));
+        }
+    }
+
+    #[test]
+    fn user_events_are_passed_to_the_observer() {
+        let (mut opl, _) = MockInterface::opl();
+        let blink: Pattern<Opl, Lamp> = Pattern::new(&[
+            ActionPoint::new(0, Action::User { event: Lamp::Red }),
+            ActionPoint::new(1, Action::User { event: Lamp::Green }),
+        ]);
+        let mut sequence = Sequence::<Opl, 16, Lamp>::new(&[ActionPoint::new(
+            0,
+            Action::Repetition {
+                pattern: blink,
+                repetition_duration: 4,
+                repetition_times: 3,
+            },
+        )]);
+
+        // The events that are fast forwarded over aren't reported
+        sequence.fast_forward(&mut opl, 4).unwrap();
+
+        let mut lamps = LampSwitch::default();
+        let mut tick = 4;
+        while sequence.run_observed(&mut opl, tick, &mut lamps).unwrap() {
+            tick += 1;
+        }
+
+        assert_eq!(
+            lamps.lit,
+            vec![(Lamp::Red, 4), (Lamp::Green, 5), (Lamp::Red, 8), (Lamp::Green, 9)]
+        );
+    }
+
+    #[test]
+    fn counted_jumps_repeat_from_the_marker() {
+        let sequence = TestSequence::new(&[
+            ActionPoint::new(0, play_note(0, 1)),
+            ActionPoint::new(2, Action::Marker { id: 1 }),
+            ActionPoint::new(0, play_note(1, 1)),
+            ActionPoint::new(
+                2,
+                Action::Jump {
+                    target: JumpTarget::Marker(1),
+                    times: 2,
+                },
+            ),
+            ActionPoint::new(1, play_note(2, 1)),
+        ]);
+
+        assert_eq!(
+            play(sequence, 100),
+            vec![
+                KeyEvent::on(0, 0),
+                KeyEvent::off(1, 0),
+                KeyEvent::on(2, 1),
+                KeyEvent::off(3, 1),
+                KeyEvent::on(4, 1),
+                KeyEvent::off(5, 1),
+                KeyEvent::on(6, 1),
+                KeyEvent::off(7, 1),
+                KeyEvent::on(9, 2),
+                KeyEvent::off(10, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn da_capo_al_coda() {
+        const CODA: u32 = 9;
+
+        let sequence = TestSequence::new(&[
+            ActionPoint::new(0, play_note(0, 1)),
+            ActionPoint::new(2, Action::ToCoda { coda: CODA }),
+            // Held over the jump, so it must be released by it
+            ActionPoint::new(0, play_note(1, 3)),
+            ActionPoint::new(
+                2,
+                Action::Jump {
+                    target: JumpTarget::Start,
+                    times: 1,
+                },
+            ),
+            ActionPoint::new(2, Action::Marker { id: CODA }),
+            ActionPoint::new(0, play_note(2, 1)),
+        ]);
+
+        assert_eq!(
+            play(sequence, 100),
+            vec![
+                KeyEvent::on(0, 0),
+                KeyEvent::off(1, 0),
+                KeyEvent::on(2, 1),
+                KeyEvent::on(4, 0),
+                KeyEvent::off(4, 1),
+                KeyEvent::off(5, 0),
+                KeyEvent::on(6, 2),
+                KeyEvent::off(7, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn jumping_to_an_unknown_marker_fails() {
+        let (mut opl, _) = MockInterface::opl();
+        let mut sequence = TestSequence::new(&[ActionPoint::new(
+            1,
+            Action::Jump {
+                target: JumpTarget::Marker(3),
+                times: 1,
+            },
+        )]);
+
+        sequence.run(&mut opl, 0).unwrap();
+        assert!(matches!(
+            sequence.run(&mut opl, 1),
+            Err(SequenceError::UnknownMarker { id: 3 })
+        ));
+    }
+
+    fn set_instrument(channel: usize, instrument: usize) -> Action<Opl> {
+        Action::SetInstrument {
+            channel,
+            instrument,
+        }
+    }
+
+    #[test]
+    fn set_instrument_uses_the_instrument_table() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut sequence = TestSequence::new(&[
+            ActionPoint::new(0, set_instrument(0, 0)),
+            ActionPoint::new(1, set_instrument(0, 1)),
+        ])
+        .with_instruments(&[bass_instrument()]);
+
+        sequence.run(&mut opl, 0).unwrap();
+        assert!(!mock.writes().is_empty());
+
+        assert!(matches!(
+            sequence.run(&mut opl, 1),
+            Err(SequenceError::UnknownInstrument { index: 1 })
+        ));
+    }
+
+    #[test]
+    fn validate_finds_mistakes_in_patterns() {
+        let pattern = |action| Action::Repetition {
+            pattern: Pattern::new(&[ActionPoint::new(0, note_on(0)), ActionPoint::new(1, action)]),
+            repetition_duration: 2,
+            repetition_times: 2,
+        };
+        let validate = |action| {
+            TestSequence::new(&[ActionPoint::new(0, pattern(action))])
+                .with_instruments(&[bass_instrument()])
+                .validate()
+        };
+
+        assert!(validate(set_instrument(1, 0)).is_ok());
+        assert!(matches!(
+            validate(set_instrument(1, 1)),
+            Err(SequenceError::UnknownInstrument { index: 1 })
+        ));
+        assert!(matches!(
+            validate(note_on(CHANNELS)),
+            Err(SequenceError::InvalidChannel { channel: CHANNELS })
+        ));
+        assert!(matches!(
+            validate(Action::ToCoda { coda: 2 }),
+            Err(SequenceError::UnknownMarker { id: 2 })
+        ));
+        assert!(matches!(
+            validate(Action::SetTempo { bpm: 0 }),
+            Err(SequenceError::InvalidTempo)
+        ));
+        assert!(matches!(
+            validate(Action::TempoRamp {
+                from_bpm: 120,
+                to_bpm: 0,
+                duration: 4,
+                curve: Curve::Linear,
+            }),
+            Err(SequenceError::InvalidTempo)
+        ));
+    }
+
+    #[test]
+    fn tempos_of_0_bpm_are_kept_at_1() {
+        let (mut opl, _) = MockInterface::opl();
+        let mut sequence = TestSequence::new(&[ActionPoint::new(0, Action::SetTempo { bpm: 0 })]);
+
+        sequence.run(&mut opl, 0).unwrap();
+        assert_eq!(sequence.take_tempo_change(), Some(1));
+    }
+
+    #[test]
+    fn velocity_sets_the_levels_of_the_instrument() {
+        let (mut opl, mock) = MockInterface::opl();
+        let note = |velocity| Action::PlayNote {
+            channel: 4,
+            value: Note::A(4),
+            velocity,
+            duration: 1,
+        };
+        let mut sequence = TestSequence::new(&[
+            ActionPoint::new(0, set_instrument(4, 0)),
+            ActionPoint::new(1, note(MP)),
+            ActionPoint::new(1, note(MP)),
+            ActionPoint::new(1, note(MAX_VELOCITY)),
+        ])
+        .with_instruments(&[Instrument::new(bass_instrument()).with_levels(Levels::fm(16, 0))]);
+
+        // Setting up the instrument writes the levels it starts with
+        sequence.run(&mut opl, 0).unwrap();
+
+        let mut level_writes = Vec::new();
+        for tick in 1..5 {
+            mock.clear();
+            sequence.run(&mut opl, tick).unwrap();
+            level_writes.extend(
+                mock.writes()
+                    .iter()
+                    .filter(|write| (0x40..0x56).contains(&write.address))
+                    .map(|write| (tick, write.address, write.value)),
+            );
+        }
+
+        // Channel 4 has its modulator at 0x09 and its carrier at 0x0C.
+        // The second note has the same velocity, so the levels are already right.
+        assert_eq!(
+            level_writes,
+            vec![(1, 0x49, 16), (1, 0x4C, 16), (3, 0x49, 16), (3, 0x4C, 0)]
+        );
+    }
+
+    #[test]
+    fn fades_and_the_master_volume_turn_down_held_notes() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut sequence = TestSequence::new(&[
+            ActionPoint::new(0, set_instrument(0, 0)),
+            ActionPoint::new(0, play_note(0, 20)),
+            ActionPoint::new(2, Action::FadeOut { ticks: 4 }),
+            ActionPoint::new(8, Action::FadeIn { ticks: 4 }),
+        ])
+        .with_instruments(&[Instrument::new(bass_instrument()).with_levels(Levels::fm(16, 0))]);
+
+        // The carrier of channel 0
+        let carrier_levels = |mock: &MockInterface| -> Vec<u8> {
+            mock.writes()
+                .iter()
+                .filter(|write| write.address == 0x43)
+                .map(|write| write.value)
+                .collect()
+        };
+
+        sequence.run(&mut opl, 0).unwrap();
+        sequence.set_volume(&mut opl, MP).unwrap();
+        assert_eq!(carrier_levels(&mock).last(), Some(&16));
+
+        mock.clear();
+        for tick in 1..=14 {
+            sequence.run(&mut opl, tick).unwrap();
+        }
+
+        // Every step is only written when it's different from the last one
+        let levels = carrier_levels(&mock);
+        assert_eq!(levels.len(), 8);
+        assert!(levels[..4].windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(levels[3], 63);
+        assert!(levels[4..].windows(2).all(|pair| pair[0] > pair[1]));
+        assert_eq!(levels[7], 16);
+    }
+
+    #[test]
+    fn a_new_fade_stops_the_one_before() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut sequence = TestSequence::new(&[
+            ActionPoint::new(0, set_instrument(0, 0)),
+            ActionPoint::new(0, play_note(0, 30)),
+            ActionPoint::new(2, Action::FadeOut { ticks: 20 }),
+            ActionPoint::new(4, Action::FadeIn { ticks: 4 }),
+        ])
+        .with_instruments(&[Instrument::new(bass_instrument()).with_levels(Levels::fm(16, 0))]);
+
+        for tick in 0..=6 {
+            sequence.run(&mut opl, tick).unwrap();
+        }
+
+        mock.clear();
+        for tick in 7..=24 {
+            sequence.run(&mut opl, tick).unwrap();
+        }
+
+        // Only the fade in is heard after it starts, the fade out doesn't turn the channel down again
+        let levels: Vec<u8> = mock
+            .writes()
+            .iter()
+            .filter(|write| write.address == 0x43)
+            .map(|write| write.value)
+            .collect();
+        assert_eq!(levels.len(), 4);
+        assert!(levels.windows(2).all(|pair| pair[0] > pair[1]));
+        assert_eq!(levels.last(), Some(&0));
+    }
+
+    /// Runs the sequence from tick 1 and returns the F-numbers that slides wrote to channel 0 and when
+    fn slide_f_numbers(mut sequence: TestSequence, ticks: u32) -> Vec<(u32, u16)> {
+        let (mut opl, mock) = MockInterface::opl();
+        sequence.run(&mut opl, 0).unwrap();
+        mock.clear();
+
+        for tick in 1..ticks {
+            mock.set_tick(tick);
+            sequence.run(&mut opl, tick).unwrap();
+        }
+
+        mock.writes()
+            .windows(2)
+            .filter(|pair| pair[0].address == 0xA0 && pair[1].address == 0xB0)
+            .map(|pair| (pair[0].tick, pair[0].value as u16 | (pair[1].value as u16 & 0b11) << 8))
+            .collect()
+    }
+
+    #[test]
+    fn portamento_slides_to_the_next_note() {
+        let mut sequence = TestSequence::new(&[
+            ActionPoint::new(0, play_note(0, 20)),
+            ActionPoint::new(
+                1,
+                Action::Portamento {
+                    channel: 0,
+                    to: Note::B(4),
+                    duration: 4,
+                },
+            ),
+        ]);
+
+        assert_eq!(
+            slide_f_numbers(sequence.clone(), 8),
+            vec![(2, 594), (3, 611), (4, 629), (5, 647)]
+        );
+
+        let (mut opl, _) = MockInterface::opl();
+        sequence.fast_forward(&mut opl, 6).unwrap();
+        assert!(matches!(sequence.sounding_notes().next(), Some((0, Note::B(4)))));
+    }
+
+    #[test]
+    fn glissandos_go_in_semitones_and_bends_add_up() {
+        let sequence = TestSequence::new(&[
+            ActionPoint::new(0, play_note(0, 20)),
+            ActionPoint::new(
+                1,
+                Action::Glissando {
+                    channel: 0,
+                    to: Note::C(5),
+                    duration: 6,
+                },
+            ),
+            ActionPoint::new(
+                7,
+                Action::PitchBend {
+                    channel: 0,
+                    cents: -300,
+                    duration: 0,
+                },
+            ),
+        ]);
+
+        assert_eq!(
+            slide_f_numbers(sequence, 10),
+            vec![(3, 611), (5, 647), (7, 342), (8, 577)]
+        );
+    }
+
+    #[test]
+    fn slides_stop_at_the_next_note() {
+        let sequence = TestSequence::new(&[
+            ActionPoint::new(0, note_on(0)),
+            ActionPoint::new(
+                1,
+                Action::PitchBend {
+                    channel: 0,
+                    cents: 200,
+                    duration: 8,
+                },
+            ),
+            ActionPoint::new(2, note_on(0)),
+        ]);
+
+        // The new note at tick 3 writes its own frequency
+        let f_numbers: Vec<_> = slide_f_numbers(sequence, 12)
+            .into_iter()
+            .filter(|(tick, _)| *tick != 3)
+            .collect();
+        assert_eq!(f_numbers, vec![(2, 585)]);
+    }
+
+    #[test]
+    fn fades_are_not_written_while_fast_forwarding() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut sequence = TestSequence::new(&[
+            ActionPoint::new(0, set_instrument(0, 0)),
+            ActionPoint::new(0, note_on(0)),
+            ActionPoint::new(1, Action::FadeOut { ticks: 4 }),
+            ActionPoint::new(9, Action::NoteOff { channel: 0 }),
+        ])
+        .with_instruments(&[Instrument::new(bass_instrument()).with_levels(Levels::fm(16, 0))]);
+
+        sequence.run(&mut opl, 0).unwrap();
+        mock.clear();
+        sequence.fast_forward(&mut opl, 8).unwrap();
+        assert!(mock.writes().is_empty());
+
+        // The levels of the fade are written when the notes start again. Channel 0 has its carrier at 0x03.
+        sequence.start_sounding_notes(&mut opl).unwrap();
+        assert!(mock
+            .writes()
+            .iter()
+            .any(|write| write.address == 0x43 && write.value == 63));
+    }
+
+    #[test]
+    fn pitch_lanes_make_a_vibrato() {
+        let sequence = TestSequence::new(&[
+            ActionPoint::new(0, play_note(0, 20)),
+            ActionPoint::new(
+                1,
+                Action::Automate {
+                    channel: 0,
+                    lane: Lane {
+                        parameter: Parameter::Pitch,
+                        shape: Shape::Lfo {
+                            waveform: Waveform::Triangle,
+                            center: 0,
+                            depth: 100,
+                            period: 4,
+                        },
+                        duration: 8,
+                    },
+                },
+            ),
+        ]);
+
+        // A semitone up and down around A4, and back to A4 at the end
+        assert_eq!(
+            slide_f_numbers(sequence, 12),
+            vec![
+                (1, 577),
+                (2, 611),
+                (3, 577),
+                (4, 544),
+                (5, 577),
+                (6, 611),
+                (7, 577),
+                (8, 544),
+                (9, 577)
+            ]
+        );
+    }
+
+    #[test]
+    fn level_lanes_change_the_levels_of_the_instrument() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut sequence = TestSequence::new(&[
+            ActionPoint::new(0, set_instrument(4, 0)),
+            ActionPoint::new(0, note_on(4)),
+            ActionPoint::new(
+                1,
+                Action::Automate {
+                    channel: 4,
+                    lane: Lane {
+                        parameter: Parameter::ModulatorLevel,
+                        shape: Shape::Ramp {
+                            from: 16,
+                            to: 40,
+                            curve: Curve::Linear,
+                        },
+                        duration: 4,
+                    },
+                },
+            ),
+        ])
+        .with_instruments(&[Instrument::new(bass_instrument()).with_levels(Levels::fm(16, 0))]);
+
+        sequence.run(&mut opl, 0).unwrap();
+        mock.clear();
+        for tick in 1..8 {
+            mock.set_tick(tick);
+            sequence.run(&mut opl, tick).unwrap();
+        }
+
+        let modulator_levels: Vec<_> = mock
+            .writes()
+            .iter()
+            .filter(|write| write.address == 0x49)
+            .map(|write| (write.tick, write.value))
+            .collect();
+        assert_eq!(
+            modulator_levels,
+            vec![(1, 16), (2, 22), (3, 28), (4, 34), (5, 40)]
+        );
+    }
+
+    fn effect(channel: usize, effect: Effect, duration: u32) -> Action<Opl> {
+        Action::Effect {
+            channel,
+            effect,
+            duration,
+        }
+    }
+
+    #[test]
+    fn arpeggios_go_back_to_the_note() {
+        let sequence = TestSequence::new(&[
+            ActionPoint::new(0, play_note(0, 20)),
+            ActionPoint::new(1, effect(0, Effect::arpeggio(0x47), 4)),
+        ]);
+
+        // A4, C#5, E5 and A4 again, which is written once more when the row is over
+        assert_eq!(
+            slide_f_numbers(sequence, 8),
+            vec![(1, 577), (2, 363), (3, 432), (4, 577), (5, 577)]
+        );
+    }
+
+    #[test]
+    fn effects_retrigger_cut_and_delay_notes() {
+        let sequence = TestSequence::new(&[
+            ActionPoint::new(0, effect(0, Effect::Retrigger { interval: 2 }, 6)),
+            ActionPoint::new(0, play_note(0, 8)),
+            ActionPoint::new(0, effect(1, Effect::NoteCut { ticks: 3 }, 6)),
+            ActionPoint::new(0, play_note(1, 8)),
+            ActionPoint::new(0, effect(2, Effect::NoteDelay { ticks: 2 }, 6)),
+            ActionPoint::new(0, play_note(2, 4)),
+        ]);
+
+        assert_eq!(
+            play(sequence, 100),
+            vec![
+                KeyEvent::on(0, 0),
+                KeyEvent::on(0, 1),
+                KeyEvent::off(2, 0),
+                KeyEvent::on(2, 0),
+                KeyEvent::on(2, 2),
+                KeyEvent::off(3, 1),
+                KeyEvent::off(4, 0),
+                KeyEvent::on(4, 0),
+                KeyEvent::off(6, 2),
+                KeyEvent::off(8, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn volume_slides_change_the_velocity_every_tick() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut sequence = TestSequence::new(&[
+            ActionPoint::new(0, set_instrument(4, 0)),
+            ActionPoint::new(0, note_on(4)),
+            ActionPoint::new(1, effect(4, Effect::VolumeSlide { step: -20 }, 3)),
+        ])
+        .with_instruments(&[Instrument::new(bass_instrument()).with_levels(Levels::fm(16, 0))]);
+
+        sequence.run(&mut opl, 0).unwrap();
+        mock.clear();
+        for tick in 1..8 {
+            mock.set_tick(tick);
+            sequence.run(&mut opl, tick).unwrap();
+        }
+
+        // The first tick of the row is left alone, like in a tracker
+        let carrier_levels: Vec<_> = mock
+            .writes()
+            .iter()
+            .filter(|write| write.address == 0x4C)
+            .map(|write| (write.tick, write.value))
+            .collect();
+        assert_eq!(carrier_levels, vec![(2, 4), (3, 9)]);
+    }
+
+    #[test]
+    fn retriggers_keep_the_bend_of_the_note() {
+        let mut sequence = TestSequence::new(&[
+            ActionPoint::new(0, note_on(0)),
+            ActionPoint::new(
+                1,
+                Action::PitchBend {
+                    channel: 0,
+                    cents: 100,
+                    duration: 0,
+                },
+            ),
+            ActionPoint::new(1, effect(0, Effect::Retrigger { interval: 2 }, 4)),
+        ]);
+
+        // The retrigger starts the note again and bends it right away
+        let f_numbers = slide_f_numbers(sequence.clone(), 8);
+        assert_eq!(f_numbers.first(), Some(&(1, 611)));
+        assert_eq!(f_numbers.last(), Some(&(4, 611)));
+
+        let (mut opl, mock) = MockInterface::opl();
+        for tick in 0..8 {
+            mock.set_tick(tick);
+            sequence.run(&mut opl, tick).unwrap();
+        }
+        assert_eq!(
+            mock.key_events(),
+            vec![KeyEvent::on(0, 0), KeyEvent::off(4, 0), KeyEvent::on(4, 0)]
+        );
+    }
+
+    #[test]
+    fn jumps_leave_the_state_of_the_channels_behind() {
+        let sequence = TestSequence::new(&[
+            ActionPoint::new(0, Action::Marker { id: 1 }),
+            ActionPoint::new(0, note_on(0)),
+            ActionPoint::new(
+                2,
+                Action::PitchBend {
+                    channel: 0,
+                    cents: 100,
+                    duration: 0,
+                },
+            ),
+            ActionPoint::new(
+                1,
+                Action::Automate {
+                    channel: 0,
+                    lane: Lane {
+                        parameter: Parameter::Pitch,
+                        shape: Shape::Ramp {
+                            from: 50,
+                            to: 50,
+                            curve: Curve::Linear,
+                        },
+                        duration: 0,
+                    },
+                },
+            ),
+            ActionPoint::new(
+                1,
+                Action::Jump {
+                    target: JumpTarget::Marker(1),
+                    times: 1,
+                },
+            ),
+        ]);
+
+        // The bend after the jump doesn't get the pitch offset of the lane from before the jump
+        let bends: Vec<_> = slide_f_numbers(sequence, 10)
+            .into_iter()
+            .filter(|(tick, _)| *tick == 2 || *tick == 6)
+            .collect();
+        assert_eq!(bends, vec![(2, 611), (6, 611)]);
+    }
+
+    #[test]
+    fn jumps_back_to_a_marker_start_from_its_snapshot() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut sequence = TestSequence::new(&[
+            ActionPoint::new(
+                0,
+                Action::Custom {
+                    function: |opl: &mut Opl| SynthBackend::write_register(opl, 0x01, 0x20),
+                },
+            ),
+            ActionPoint::new(0, set_instrument(0, 0)),
+            ActionPoint::new(1, Action::Marker { id: 1 }),
+            ActionPoint::new(0, play_note(0, 1)),
+            ActionPoint::new(1, set_instrument(0, 1)),
+            ActionPoint::new(
+                1,
+                Action::Jump {
+                    target: JumpTarget::Marker(1),
+                    times: 1,
+                },
+            ),
+        ])
+        .with_instruments(&[bass_instrument(), bass_instrument()]);
+
+        let mut tick = 0;
+        mock.set_tick(tick);
+        while sequence.run(&mut opl, tick).unwrap() {
+            tick += 1;
+            mock.set_tick(tick);
+        }
+
+        // Nothing before the marker runs again, but the instrument that was set at the marker is set up again at the jump.
+        // Setting up an instrument on channel 0 writes 0xC0.
+        let writes = mock.writes();
+        assert_eq!(writes.iter().filter(|write| write.address == 0x01).count(), 1);
+        assert_eq!(
+            writes
+                .iter()
+                .filter(|write| write.address == 0xC0)
+                .map(|write| write.tick)
+                .collect::<Vec<_>>(),
+            vec![0, 2, 3, 4]
+        );
+        assert_eq!(
+            mock.key_events(),
+            vec![KeyEvent::on(1, 0), KeyEvent::off(2, 0), KeyEvent::on(3, 0), KeyEvent::off(4, 0)]
+        );
+    }
+
+    fn late_sequence(late_policy: LatePolicy) -> TestSequence {
+        let mut sequence = TestSequence::new(&[
+            ActionPoint::new(2, play_note(0, 4)),
+            ActionPoint::new(1, play_note(1, 4)),
+            ActionPoint::new(2, play_note(2, 4)),
+        ]);
+        sequence.set_late_policy(late_policy);
+        sequence
+    }
+
+    /// Runs the sequence at tick 0, 4 and then every tick until the end
+    fn play_with_gap(mut sequence: TestSequence) -> (Vec<KeyEvent>, u32) {
+        let (mut opl, mock) = MockInterface::opl();
+
+        for tick in [0, 4].iter().copied().chain(5..100) {
+            mock.set_tick(tick);
+            if !sequence.run(&mut opl, tick).unwrap() {
+                let mut events = mock.key_events();
+                events.sort_by_key(|e| (e.tick, e.channel));
+                return (events, sequence.late_events());
+            }
+        }
+
+        panic!("Sequence didn't finish");
+    }
+
+    #[test]
+    fn late_events_run_late() {
+        let (events, late_events) = play_with_gap(late_sequence(LatePolicy::RunLate));
+
+        assert_eq!(
+            events,
+            vec![KeyEvent::on(4, 0), KeyEvent::on(4, 1), KeyEvent::on(5, 2), KeyEvent::off(6, 0), KeyEvent::off(7, 1), KeyEvent::off(9, 2)]
+        );
+        // Both play notes and both of their note ons
+        assert_eq!(late_events, 4);
+    }
+
+    #[test]
+    fn late_events_are_dropped() {
+        let (events, late_events) = play_with_gap(late_sequence(LatePolicy::Drop));
+
+        assert_eq!(events, vec![KeyEvent::on(5, 2), KeyEvent::off(9, 2)]);
+        assert_eq!(late_events, 2);
+    }
+
+    #[test]
+    fn late_events_give_an_error() {
+        let (mut opl, _) = MockInterface::opl();
+        let mut sequence = late_sequence(LatePolicy::Error);
+
+        assert!(sequence.run(&mut opl, 0).unwrap());
+        assert!(matches!(
+            sequence.run(&mut opl, 4),
+            Err(SequenceError::LateEvent {
+                scheduled: 2,
+                timestamp: 4
+            })
+        ));
+        assert_eq!(sequence.late_events(), 1);
+
+        // Every run gets past the next late event
+        assert!(matches!(
+            sequence.run(&mut opl, 4),
+            Err(SequenceError::LateEvent {
+                scheduled: 3,
+                timestamp: 4
+            })
+        ));
+        assert!(sequence.run(&mut opl, 4).unwrap());
+        assert_eq!(sequence.late_events(), 2);
+    }
+
+    #[test]
+    fn tempo_changes_are_reported_once() {
+        let (mut opl, _) = MockInterface::opl();
+        let mut sequence = TestSequence::new(&[
+            ActionPoint::new(0, Action::SetTempo { bpm: 120 }),
+            ActionPoint::new(1, Action::SetTempo { bpm: 90 }),
+            ActionPoint::new(1, note_on(0)),
+        ]);
+
+        assert_eq!(sequence.tempo(), None);
+        assert_eq!(sequence.take_tempo_change(), None);
+
+        sequence.run(&mut opl, 0).unwrap();
+        assert_eq!(sequence.take_tempo_change(), Some(120));
+        assert_eq!(sequence.take_tempo_change(), None);
+
+        sequence.run(&mut opl, 1).unwrap();
+        sequence.run(&mut opl, 2).unwrap();
+        assert_eq!(sequence.tempo(), Some(90));
+        assert_eq!(sequence.take_tempo_change(), Some(90));
+    }
+
+    #[test]
+    fn tempo_ramps_change_the_tempo_every_tick() {
+        let (mut opl, _) = MockInterface::opl();
+        let mut sequence = TestSequence::new(&[ActionPoint::new(
+            2,
+            Action::TempoRamp {
+                from_bpm: 100,
+                to_bpm: 60,
+                duration: 4,
+                curve: Curve::Linear,
+            },
+        )]);
+
+        let mut tempos = Vec::new();
+        let mut tick = 0;
+        while sequence.run(&mut opl, tick).unwrap() {
+            tempos.push(sequence.take_tempo_change());
+            tick += 1;
+        }
+        tempos.push(sequence.take_tempo_change());
+
+        assert_eq!(
+            tempos,
+            vec![None, None, Some(100), Some(90), Some(80), Some(70), Some(60)]
+        );
+    }
+
+    #[test]
+    fn next_timestamp_is_the_next_event() {
+        let (mut opl, _) = MockInterface::opl();
+        let mut sequence = TestSequence::new(&[
+            ActionPoint::new(3, play_note(0, 10)),
+            ActionPoint::new(4, play_note(1, 2)),
+        ]);
+
+        assert_eq!(sequence.next_timestamp(), Some(3));
+        sequence.run(&mut opl, 3).unwrap();
+        assert_eq!(sequence.next_timestamp(), Some(7));
+        sequence.run(&mut opl, 7).unwrap();
+        assert_eq!(sequence.next_timestamp(), Some(9));
+        sequence.run(&mut opl, 9).unwrap();
+        assert_eq!(sequence.next_timestamp(), Some(13));
+        sequence.run(&mut opl, 13).unwrap();
+        assert_eq!(sequence.next_timestamp(), None);
+    }
+
+    #[test]
+    fn run_timestamps_can_wrap_around() {
+        let (mut opl, mock) = MockInterface::opl();
+        let mut sequence = TestSequence::new(&[
+            ActionPoint::new(0, play_note(0, 2)),
+            ActionPoint::new(1, play_note(1, 2)),
+        ]);
+        sequence.start_at(u32::MAX - 1);
+
+        for (tick, timestamp) in [u32::MAX - 1, u32::MAX, 0, 1, 2].iter().enumerate() {
+            mock.set_tick(tick as u32);
+            sequence.run(&mut opl, *timestamp).unwrap();
+        }
+
+        assert_eq!(
+            mock.key_events(),
+            vec![KeyEvent::on(0, 0), KeyEvent::on(1, 1), KeyEvent::off(2, 0), KeyEvent::off(3, 1)]
+        );
+        assert_eq!(sequence.next_timestamp(), None);
+    }
+}
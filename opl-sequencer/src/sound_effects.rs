@@ -0,0 +1,20 @@
+//! Short sound effects for the [SfxLayer](crate::layer::SfxLayer).
+//!
+//! Effects are timed in milliseconds, not in ticks.
+
+use crate::backend::SynthBackend;
+use crate::dynamics::FFF;
+use crate::sequencer::{Action, ActionPoint, Sequence};
+use opl_driver::hl::Note;
+
+/// Three quick rising notes
+pub fn blip<B: SynthBackend, const N: usize>(channel: usize) -> Sequence<B, N> {
+    #[rustfmt::skip]
+    let sequence = Sequence::new(&[
+        ActionPoint::new(0 , Action::PlayNote { channel, value: Note::C(6), velocity: FFF, duration: 40 }),
+        ActionPoint::new(40, Action::PlayNote { channel, value: Note::E(6), velocity: FFF, duration: 40 }),
+        ActionPoint::new(40, Action::PlayNote { channel, value: Note::G(6), velocity: FFF, duration: 80 }),
+    ]);
+
+    sequence
+}
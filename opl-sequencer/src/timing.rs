@@ -0,0 +1,205 @@
+/// Converts the ticks of a sequence to timer counts.
+///
+/// The length of a tick is `timer_hz * 60 / (bpm * ticks_per_beat)` timer counts, which is rarely a whole number.
+/// The clock always gives whole counts and the fractions that are left over are carried to the next time.
+/// So a single tick may be up to one count off, but on average the ticks are exactly at the requested tempo.
+pub struct TickClock {
+    timer_hz: u32,
+    ticks_per_beat: u32,
+    bpm: u32,
+    /// The fraction of a count that is carried over, in units of `bpm * ticks_per_beat`
+    remainder: u64,
+}
+
+impl TickClock {
+    pub fn new(timer_hz: u32, ticks_per_beat: u32, bpm: u32) -> Self {
+        assert!(bpm > 0, "The tempo must be at least 1 bpm");
+
+        Self {
+            timer_hz,
+            ticks_per_beat,
+            bpm,
+            remainder: 0,
+        }
+    }
+
+    pub fn bpm(&self) -> u32 {
+        self.bpm
+    }
+
+    pub fn set_bpm(&mut self, bpm: u32) {
+        assert!(bpm > 0, "The tempo must be at least 1 bpm");
+
+        // The remainder is less than a count, so it's fine to let it go
+        self.bpm = bpm;
+        self.remainder = 0;
+    }
+
+    /// The exact amount of ticks per second at the current tempo
+    pub fn ticks_per_second(&self) -> f32 {
+        (self.bpm * self.ticks_per_beat) as f32 / 60.0
+    }
+
+    /// The amount of timer counts it takes to advance the given amount of ticks
+    pub fn advance(&mut self, ticks: u32) -> u64 {
+        let numerator = self.timer_hz as u64 * 60;
+        let denominator = self.bpm as u64 * self.ticks_per_beat as u64;
+
+        self.remainder += numerator * ticks as u64;
+        let counts = self.remainder / denominator;
+        self.remainder %= denominator;
+
+        counts
+    }
+
+    /// The most ticks that [Self::advance] can go at the current tempo without taking more than the amount of timer counts.
+    /// The fraction that is carried over is less than a count, so this holds whatever it is.
+    pub fn ticks_within(&self, counts: u64) -> u32 {
+        let numerator = self.timer_hz as u64 * 60;
+        let denominator = self.bpm as u64 * self.ticks_per_beat as u64;
+
+        (counts.saturating_mul(denominator) / numerator).min(u32::MAX as u64) as u32
+    }
+}
+
+/// Keeps track of how late something runs compared to when it was scheduled.
+///
+/// The unit is up to the user, e.g. the cycles of the core clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Jitter {
+    min: u32,
+    max: u32,
+    total: u64,
+    count: u32,
+}
+
+impl Jitter {
+    pub const fn new() -> Self {
+        Self {
+            min: u32::MAX,
+            max: 0,
+            total: 0,
+            count: 0,
+        }
+    }
+
+    pub fn record(&mut self, lateness: u32) {
+        self.min = self.min.min(lateness);
+        self.max = self.max.max(lateness);
+        self.total += lateness as u64;
+        self.count += 1;
+    }
+
+    /// The amount of measurements
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// The smallest lateness, or `None` if nothing was recorded yet
+    pub fn min(&self) -> Option<u32> {
+        if self.count > 0 {
+            Some(self.min)
+        } else {
+            None
+        }
+    }
+
+    /// The biggest lateness, or `None` if nothing was recorded yet
+    pub fn max(&self) -> Option<u32> {
+        if self.count > 0 {
+            Some(self.max)
+        } else {
+            None
+        }
+    }
+
+    /// The average lateness, or `None` if nothing was recorded yet
+    pub fn mean(&self) -> Option<u32> {
+        if self.count > 0 {
+            Some((self.total / self.count as u64) as u32)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::QUARTER;
+
+    #[test]
+    fn periods_add_up_to_the_exact_tempo() {
+        let mut clock = TickClock::new(1_000_000, QUARTER, 178);
+
+        // 178 beats take exactly one minute
+        let periods = (0..178 * QUARTER).map(|_| clock.advance(1));
+        let mut total = 0;
+        for period in periods {
+            assert!(period == 10533 || period == 10534, "{}", period);
+            total += period;
+        }
+
+        assert_eq!(total, 60_000_000);
+        assert_eq!(clock.advance(178 * QUARTER), 60_000_000);
+    }
+
+    #[test]
+    fn advancing_multiple_ticks_is_the_same_as_one_by_one() {
+        let mut one_by_one = TickClock::new(168_000_000, QUARTER, 178);
+        let mut at_once = TickClock::new(168_000_000, QUARTER, 178);
+
+        for ticks in [1, 5, 3, 100, 7].iter() {
+            let expected: u64 = (0..*ticks).map(|_| one_by_one.advance(1)).sum();
+            assert_eq!(at_once.advance(*ticks), expected);
+        }
+    }
+
+    #[test]
+    fn whole_periods_are_not_dithered() {
+        let mut clock = TickClock::new(1_000_000, QUARTER, 120);
+
+        for _ in 0..100 {
+            assert_eq!(clock.advance(1), 15625);
+        }
+    }
+
+    #[test]
+    fn tempo_changes_take_effect_on_the_next_tick() {
+        let mut clock = TickClock::new(1_000_000, QUARTER, 120);
+        assert_eq!(clock.advance(1), 15625);
+
+        clock.set_bpm(60);
+        assert_eq!(clock.bpm(), 60);
+        assert_eq!(clock.advance(1), 31250);
+    }
+
+    #[test]
+    fn ticks_within_stay_within_the_counts() {
+        let mut clock = TickClock::new(1_000_000, QUARTER, 178);
+
+        // Leaves a fraction to carry over
+        clock.advance(1);
+        assert_eq!(clock.ticks_within(100_000), 9);
+        assert!(clock.advance(9) <= 100_000);
+
+        // Even at 1 bpm a tick of a 168 MHz clock is less than 2^31 cycles
+        let slow = TickClock::new(168_000_000, QUARTER, 1);
+        assert_eq!(slow.ticks_within((1 << 31) - 1), 6);
+    }
+
+    #[test]
+    fn jitter_keeps_the_extremes_and_the_mean() {
+        let mut jitter = Jitter::new();
+        assert_eq!(jitter.mean(), None);
+
+        for lateness in [40, 10, 100, 50].iter() {
+            jitter.record(*lateness);
+        }
+
+        assert_eq!(jitter.count(), 4);
+        assert_eq!(jitter.min(), Some(10));
+        assert_eq!(jitter.max(), Some(100));
+        assert_eq!(jitter.mean(), Some(50));
+    }
+}
@@ -0,0 +1,54 @@
+use stm32f4xx_hal::hal::blocking::delay::{DelayMs, DelayUs};
+
+/// A busy-waiting delay that counts core cycles.
+///
+/// The HAL delay needs SysTick, but RTIC uses that for scheduling tasks.
+pub struct CycleDelay {
+    cycles_per_us: u32,
+}
+
+impl CycleDelay {
+    pub fn new(sysclk_hz: u32) -> Self {
+        Self {
+            cycles_per_us: sysclk_hz / 1_000_000,
+        }
+    }
+}
+
+impl DelayUs<u32> for CycleDelay {
+    fn delay_us(&mut self, us: u32) {
+        cortex_m::asm::delay(us.saturating_mul(self.cycles_per_us));
+    }
+}
+
+impl DelayUs<u16> for CycleDelay {
+    fn delay_us(&mut self, us: u16) {
+        self.delay_us(us as u32);
+    }
+}
+
+impl DelayUs<u8> for CycleDelay {
+    fn delay_us(&mut self, us: u8) {
+        self.delay_us(us as u32);
+    }
+}
+
+impl DelayMs<u32> for CycleDelay {
+    fn delay_ms(&mut self, ms: u32) {
+        for _ in 0..ms {
+            self.delay_us(1000u32);
+        }
+    }
+}
+
+impl DelayMs<u16> for CycleDelay {
+    fn delay_ms(&mut self, ms: u16) {
+        self.delay_ms(ms as u32);
+    }
+}
+
+impl DelayMs<u8> for CycleDelay {
+    fn delay_ms(&mut self, ms: u8) {
+        self.delay_ms(ms as u32);
+    }
+}
@@ -11,22 +11,32 @@ use cortex_m_rt::{exception, ExceptionFrame};
 use opl_driver::ll::VibratoDepth;
 use opl_driver::{
     hl::Melody,
-    hl::Opl2Error,
     ll::{Bit, ShiftInterface},
 };
+use opl_sequencer::{
+    layer::{Layered, SfxLayer},
+    mission_impossible,
+    observer::Observer,
+    player::{EndBehaviour, Player},
+    sound_effects,
+    timing::{Jitter, TickClock},
+    QUARTER,
+};
+use rtic::cyccnt::{Instant, U32Ext};
+use rtic::Mutex;
 use rtt_target::{rprintln, rtt_init, set_print_channel};
-use sequencer::{Action, ActionPoint, Sequence};
 use spi::{NoMiso, Spi};
+use cortex_m::peripheral::DWT;
+use cycle_delay::CycleDelay;
 use stm32f4xx_hal::{
-    delay::Delay, gpio::gpioa::PA2, gpio::gpioa::PA3, gpio::gpioa::PA4, gpio::gpioa::PA5,
+    gpio::gpioa::PA2, gpio::gpioa::PA3, gpio::gpioa::PA4, gpio::gpioa::PA5,
     gpio::gpioa::PA6, gpio::gpioa::PA7, gpio::Alternate, gpio::OpenDrain, gpio::Output,
-    gpio::PushPull, gpio::AF5, hal::spi::MODE_0, spi, stm32::SPI1, timer::Timer,
+    gpio::PushPull, gpio::AF5, hal::spi::MODE_0, spi, stm32::SPI1,
 };
-use stm32f4xx_hal::{prelude::*, stm32::TIM4};
+use stm32f4xx_hal::prelude::*;
 
+mod cycle_delay;
 mod helpers;
-mod mission_impossible;
-mod sequencer;
 
 type Led2Pin = PA6<Output<OpenDrain>>;
 
@@ -36,35 +46,60 @@ type Opl<S> = opl_driver::hl::Opl2<
         PA4<Output<PushPull>>,
         PA3<Output<PushPull>>,
         PA2<Output<PushPull>>,
-        Delay,
+        CycleDelay,
     >,
     S,
 >;
 
-#[global_allocator]
-static ALLOCATOR: CortexMHeap = CortexMHeap::empty();
+/// The synth as the music sees it, with the channels the sound effects borrow taken out
+type Music = Layered<Opl<Melody>>;
+
+/// The tempo that is used when the song doesn't set one
+const DEFAULT_BPM: u32 = 120;
+
+/// What to do when the song is over
+const END_BEHAVIOUR: EndBehaviour = EndBehaviour::Stop;
+
+/// The amount of events the music sequence can have queued up at the same time
+const SEQUENCE_CAPACITY: usize = 32;
+
+/// The amount of events a sound effect can have queued up at the same time
+const SFX_CAPACITY: usize = 4;
 
-pub const FULL: u32 = 128;
-pub const HALF: u32 = 64;
-pub const QUARTER: u32 = 32;
-pub const EIGHTH: u32 = 16;
-pub const SIXTEENTH: u32 = 8;
+/// The core clock, which is also the rate of the CYCCNT monotonic
+const CLOCK_SPEED: u32 = 168_000_000;
 
-const BPM: u32 = 178;
+/// A schedule on the CYCCNT has to be less than 2^31 cycles away, so the player can't sleep longer than this in one go
+const MAX_WAKEUP_CYCLES: u64 = (1 << 31) - 1;
+
+/// The sound effects are timed in milliseconds
+const CYCLES_PER_MS: u32 = CLOCK_SPEED / 1000;
+
+/// How long a tick waits to be handed over again when the player is still busy with the last one
+const TICK_RETRY_CYCLES: u32 = CYCLES_PER_MS / 10;
+
+#[global_allocator]
+static ALLOCATOR: CortexMHeap = CortexMHeap::empty();
 
 #[rtic::app(device = stm32f4xx_hal::stm32, peripherals = true, monotonic = rtic::cyccnt::CYCCNT)]
 const APP: () = {
     struct Resources {
-        global_timer: Timer<TIM4>,
+        clock: TickClock,
+        #[init(Jitter::new())]
+        tick_jitter: Jitter,
+        #[init(Jitter::new())]
+        play_jitter: Jitter,
         led_2: Led2Pin,
-        opl: Opl<Melody>,
-        music_sequence: Sequence<Opl<Melody>, Opl2Error>,
+        opl: Music,
+        music_player: Player<Music, SEQUENCE_CAPACITY>,
+        #[init(SfxLayer::new())]
+        sfx: SfxLayer<Opl<Melody>, SFX_CAPACITY>,
     }
 
-    #[init()]
+    #[init(schedule = [on_tick])]
     fn init(cx: init::Context) -> init::LateResources {
-        // Cortex-M peripherals
-        let cp: cortex_m::Peripherals = cx.core;
+        // Cortex-M peripherals. SysTick is not in here because RTIC uses it for the schedule.
+        let mut cp: rtic::Peripherals = cx.core;
 
         // Device specific peripherals
         let dp: stm32f4xx_hal::stm32::Peripherals = cx.device;
@@ -91,8 +126,16 @@ const APP: () = {
             HEAP_SIZE as usize / 1024
         );
 
+        // The CYCCNT monotonic needs the cycle counter
+        cp.DCB.enable_trace();
+        DWT::unlock();
+        cp.DWT.enable_cycle_counter();
+
+        // Keeps the debug clocks running during the WFI in idle, so the debug link (and with it RTT) doesn't drop out.
+        // It doesn't change how the core sleeps or wakes up.
+        dp.DBGMCU.cr.modify(|_, w| w.dbg_sleep().set_bit());
+
         let rcc = dp.RCC.constrain();
-        const CLOCK_SPEED: u32 = 168_000_000;
         let clocks = rcc
             .cfgr
             .use_hse(8.mhz())
@@ -109,16 +152,6 @@ const APP: () = {
         let mut led_2: Led2Pin = gpioa.pa6.into_open_drain_output();
         led_2.set_high().unwrap();
 
-        let ticks_per_second = BPM * QUARTER / 60;
-        rprintln!(
-            "Music at {}({}) bpm and {} ticks per second",
-            BPM,
-            ticks_per_second * 60,
-            ticks_per_second
-        );
-        let mut global_timer = Timer::tim4(dp.TIM4, ticks_per_second.hz(), clocks);
-        global_timer.listen(stm32f4xx_hal::timer::Event::TimeOut);
-
         // Setup opl hardware
         let opl_spi = spi::Spi::spi1(
             dp.SPI1,
@@ -134,7 +167,7 @@ const APP: () = {
         let opl_a0 = gpioa.pa4.into_push_pull_output();
         let opl_latch = gpioa.pa3.into_push_pull_output();
         let opl_reset = gpioa.pa2.into_push_pull_output();
-        let opl_delay = stm32f4xx_hal::delay::Delay::new(cp.SYST, clocks);
+        let opl_delay = CycleDelay::new(clocks.sysclk().0);
 
         let opl = opl_driver::hl::Opl2::new(opl_driver::ll::ShiftInterface::new(
             opl_spi, opl_a0, opl_latch, opl_reset, opl_delay,
@@ -151,71 +184,196 @@ const APP: () = {
             .write(|w| w.vibrato_depth(VibratoDepth::High))
             .unwrap();
 
-        const BASS: usize = 0;
-        const MELODY: usize = 1;
-        const CHORD0: usize = 2;
-        const CHORD1: usize = 3;
-        const CHORD2: usize = 4;
-
-        #[rustfmt::skip]
-        let music_sequence: Sequence<Opl<Melody>, Opl2Error> = Sequence::new(&[
-            ActionPoint::new(0, Action::Custom { function: |opl| opl.setup_melody_instrument(BASS, mission_impossible::bass_instrument()) }),
-            ActionPoint::new(0, Action::Custom { function: |opl| opl.setup_melody_instrument(MELODY, mission_impossible::motiv_instrument()) }),
-            ActionPoint::new(0, Action::Custom { function: |opl| opl.setup_melody_instrument(CHORD0, mission_impossible::chord_fill_instrument()) }),
-            ActionPoint::new(0, Action::Custom { function: |opl| opl.setup_melody_instrument(CHORD1, mission_impossible::chord_fill_instrument()) }),
-            ActionPoint::new(0, Action::Custom { function: |opl| opl.setup_melody_instrument(CHORD2, mission_impossible::chord_fill_instrument()) }),
-
-            ActionPoint::new(QUARTER     , mission_impossible::bass_loop(6, BASS, 2)),
-            ActionPoint::new(0           , mission_impossible::bass_loop(2, MELODY, 4)),
-            ActionPoint::new(QUARTER * 20, mission_impossible::main_motiv(MELODY)),
-            ActionPoint::new(QUARTER * 20, mission_impossible::chord_fill([CHORD0,CHORD1,CHORD2])),
-            ActionPoint::new(QUARTER * 10, mission_impossible::alt_motiv(MELODY)),
-            ActionPoint::new(QUARTER * 10, mission_impossible::bass_loop_to_alt_transition(BASS, 2)),
-            ActionPoint::new(QUARTER * 10, mission_impossible::main_motiv_low(MELODY)),
-            ActionPoint::new(0           , mission_impossible::main_motiv_low(CHORD0)),
-            ActionPoint::new(0           , mission_impossible::bass_loop_alt(BASS, 2)),
-            ActionPoint::new(QUARTER * 20, mission_impossible::bass_loop(1, BASS, 2)),
-            ActionPoint::new(0           , mission_impossible::alt_motiv_no_delay(MELODY)),
-            ActionPoint::new(QUARTER * 10, Action::Custom { function: |opl| opl.setup_melody_instrument(CHORD0, mission_impossible::motiv_instrument()) }),
-            ActionPoint::new(0           , mission_impossible::bass_finisher(BASS, CHORD0, 2, 3)),
-            ActionPoint::new(QUARTER * 5 , mission_impossible::motiv_finisher([MELODY, CHORD1, CHORD2], [4, 3, 3])),
-        ]);
+        let mut opl = Layered::new(opl);
+
+        let mut music_player: Player<Music, SEQUENCE_CAPACITY> = Player::new(mission_impossible::song());
+        music_player.set_end_behaviour(END_BEHAVIOUR);
+        music_player.play(&mut opl).unwrap();
+
+        // Run the first tick already so the song can set its own tempo before the clock starts
+        music_player.advance(&mut opl, 0).unwrap();
+        let bpm = music_player.take_tempo_change().unwrap_or(DEFAULT_BPM);
+
+        let mut clock = TickClock::new(CLOCK_SPEED, QUARTER, bpm);
+        rprintln!(
+            "Music at {} bpm and {} ticks per second",
+            clock.bpm(),
+            clock.ticks_per_second()
+        );
+
+        if let Some((cycles, ticks)) = next_wakeup(&music_player, &mut clock) {
+            cx.schedule.on_tick(cx.start + cycles.cycles(), ticks).unwrap();
+        }
 
         init::LateResources {
-            global_timer,
+            clock,
             led_2,
             opl,
-            music_sequence,
+            music_player,
         }
     }
 
     #[idle]
     fn idle(_cx: idle::Context) -> ! {
+        // Everything happens in the scheduled tasks, so we can sleep until the next one
         loop {
-            cortex_m::asm::nop();
+            cortex_m::asm::wfi();
         }
     }
 
-    #[task(binds = TIM4, resources = [global_timer, led_2, opl, music_sequence])]
-    fn on_global_timer(cx: on_global_timer::Context) {
-        static mut COUNT: u32 = 0;
+    /// Fires when the next tick is due and hands it over to the player.
+    ///
+    /// This runs at the highest priority so the time is measured right, but it should only ever do that.
+    /// Anything slow goes in `play` so that other interrupts can go before it.
+    #[task(priority = 3, resources = [tick_jitter], schedule = [on_tick], spawn = [play])]
+    fn on_tick(cx: on_tick::Context, ticks: u32) {
+        let lateness = Instant::now().duration_since(cx.scheduled).as_cycles();
+        cx.resources.tick_jitter.record(lateness);
+
+        // Only play schedules this task and it does so as the last thing it does, so play isn't waiting to run here.
+        // If it ever is, the tick is handed over again a bit later, because nothing else would schedule the next one.
+        if let Err((ticks, _)) = cx.spawn.play(ticks, cx.scheduled) {
+            cx.schedule
+                .on_tick(cx.scheduled + TICK_RETRY_CYCLES.cycles(), ticks)
+                .unwrap();
+        }
+    }
+
+    /// Advances the player by the given amount of ticks and schedules the tick of the next event
+    #[task(priority = 1, resources = [clock, tick_jitter, play_jitter, led_2, opl, music_player], schedule = [on_tick])]
+    fn play(cx: play::Context, ticks: u32, scheduled: Instant) {
+        cx.resources
+            .play_jitter
+            .record(Instant::now().duration_since(scheduled).as_cycles());
 
-        let global_timer: &mut Timer<TIM4> = cx.resources.global_timer;
+        let clock: &mut TickClock = cx.resources.clock;
         let led_2: &mut Led2Pin = cx.resources.led_2;
-        let opl: &mut Opl<Melody> = cx.resources.opl;
-        let music_sequence: &mut Sequence<Opl<Melody>, Opl2Error> = cx.resources.music_sequence;
+        let opl: &mut Music = cx.resources.opl;
+        let music_player: &mut Player<Music, SEQUENCE_CAPACITY> = cx.resources.music_player;
+
+        let mut observer = MusicObserver { led_2 };
+
+        let playing = music_player.advance_observed(opl, ticks, &mut observer).unwrap();
+
+        if !playing {
+            #[cfg(feature = "trace")]
+            {
+                let mut tick_jitter = cx.resources.tick_jitter;
+                rprintln!(
+                    "Song done with {} late events",
+                    music_player.sequence().late_events()
+                );
+                print_jitter("Tick", &tick_jitter.lock(|jitter| *jitter));
+                print_jitter("Play", cx.resources.play_jitter);
+            }
+            return;
+        }
+
+        if let Some(bpm) = music_player.take_tempo_change() {
+            rprintln!("Tempo change to {} bpm", bpm);
+            clock.set_bpm(bpm);
+        }
+
+        if let Some((cycles, ticks)) = next_wakeup(music_player, clock) {
+            // Scheduled relative to when this tick was due, not when it ran, so the delays don't add up
+            cx.schedule.on_tick(scheduled + cycles.cycles(), ticks).unwrap();
+        }
+    }
+
+    /// Starts a sound effect on the channel.
+    /// The music loses the channel while the effect plays, so use one that the song doesn't, like 5 to 8 for the Mission Impossible theme.
+    #[task(priority = 1, resources = [opl, sfx], schedule = [sfx_tick])]
+    fn play_sfx(cx: play_sfx::Context, channel: usize) {
+        let opl: &mut Music = cx.resources.opl;
+        let sfx: &mut SfxLayer<Opl<Melody>, SFX_CAPACITY> = cx.resources.sfx;
 
-        global_timer.clear_interrupt(stm32f4xx_hal::timer::Event::TimeOut);
-        led_2.toggle().unwrap();
+        let was_playing = sfx.is_playing();
+        sfx.play(
+            opl,
+            channel,
+            mission_impossible::motiv_instrument(),
+            sound_effects::blip(channel),
+        )
+        .unwrap();
+        sfx.advance(opl, 0).unwrap();
+
+        // Otherwise the layer is already ticking
+        if !was_playing {
+            cx.schedule
+                .sfx_tick(Instant::now() + CYCLES_PER_MS.cycles())
+                .unwrap();
+        }
+    }
+
+    /// Runs the sound effects every millisecond for as long as there are any
+    #[task(priority = 1, resources = [opl, sfx], schedule = [sfx_tick])]
+    fn sfx_tick(cx: sfx_tick::Context) {
+        let opl: &mut Music = cx.resources.opl;
+        let sfx: &mut SfxLayer<Opl<Melody>, SFX_CAPACITY> = cx.resources.sfx;
 
-        if !music_sequence.run(opl, *COUNT).unwrap() {
-            cortex_m::asm::bkpt();
+        if sfx.advance(opl, 1).unwrap() {
+            cx.schedule
+                .sfx_tick(cx.scheduled + CYCLES_PER_MS.cycles())
+                .unwrap();
         }
+    }
 
-        *COUNT = COUNT.wrapping_add(1);
+    // Interrupts that are not used by the hardware, used to dispatch the software tasks
+    extern "C" {
+        fn EXTI0();
+        fn EXTI1();
     }
 };
 
+/// Shows where we are in the song
+struct MusicObserver<'a> {
+    led_2: &'a mut Led2Pin,
+}
+
+impl Observer for MusicObserver<'_> {
+    fn on_marker(&mut self, id: u32, timestamp: u32) {
+        let name = match id {
+            mission_impossible::INTRO => "intro",
+            mission_impossible::MAIN_MOTIV => "main motiv",
+            mission_impossible::ALT_MOTIV => "alt motiv",
+            mission_impossible::FINISHER => "finisher",
+            _ => "unknown",
+        };
+
+        rprintln!("Reached the {} at {}", name, timestamp);
+        self.led_2.toggle().unwrap();
+    }
+}
+
+/// Finds when the player has to wake up next.
+/// Gives the amount of cycles to wait and the amount of ticks to advance the player by then.
+fn next_wakeup(
+    music_player: &Player<Music, SEQUENCE_CAPACITY>,
+    clock: &mut TickClock,
+) -> Option<(u32, u32)> {
+    // A single tick fits in a wakeup at any tempo, even at the 1 bpm the sequence keeps it at
+    let max_ticks = clock.ticks_within(MAX_WAKEUP_CYCLES).max(1);
+    let ticks = music_player.ticks_to_next_event()?.clamp(1, max_ticks);
+
+    Some((clock.advance(ticks) as u32, ticks))
+}
+
+#[cfg(feature = "trace")]
+fn print_jitter(name: &str, jitter: &Jitter) {
+    const CYCLES_PER_US: u32 = CLOCK_SPEED / 1_000_000;
+
+    if let (Some(min), Some(mean), Some(max)) = (jitter.min(), jitter.mean(), jitter.max()) {
+        rprintln!(
+            "{} jitter over {} ticks: min {}us, mean {}us, max {}us",
+            name,
+            jitter.count(),
+            min / CYCLES_PER_US,
+            mean / CYCLES_PER_US,
+            max / CYCLES_PER_US
+        );
+    }
+}
+
 #[alloc_error_handler]
 fn alloc_error(layout: Layout) -> ! {
     panic!("Alloc error: {:?}", layout);
@@ -0,0 +1,2 @@
+$host_target = (rustc -vV | Select-String "host: (.+)").Matches[0].Groups[1].Value
+cargo test -p opl-sequencer --target $host_target